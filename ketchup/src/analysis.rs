@@ -0,0 +1,2653 @@
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Quota utilization at or above this percentage is reported as near the limit
+const QUOTA_NEAR_LIMIT_PERCENT: f64 = 80.0;
+
+/// Get a string field from resource metadata
+fn metadata_str<'a>(resource: &'a Value, field: &str) -> Option<&'a str> {
+    resource
+        .get("metadata")
+        .and_then(|m| m.get(field))
+        .and_then(|v| v.as_str())
+}
+
+/// Parse a Kubernetes resource quantity (e.g. "100m", "64Mi", "2") into a plain number
+pub fn parse_quantity(quantity: &str) -> Option<f64> {
+    let quantity = quantity.trim();
+    let split_at = quantity
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(quantity.len());
+    let (number, suffix) = quantity.split_at(split_at);
+    let number: f64 = number.parse().ok()?;
+
+    let multiplier = match suffix {
+        "" => 1.0,
+        "n" => 1e-9,
+        "u" => 1e-6,
+        "m" => 1e-3,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "E" => 1e18,
+        "Ki" => 1024.0,
+        "Mi" => 1024.0_f64.powi(2),
+        "Gi" => 1024.0_f64.powi(3),
+        "Ti" => 1024.0_f64.powi(4),
+        "Pi" => 1024.0_f64.powi(5),
+        "Ei" => 1024.0_f64.powi(6),
+        exponent if exponent.starts_with(['e', 'E']) => {
+            10f64.powi(exponent[1..].parse::<i32>().ok()?)
+        }
+        _ => return None,
+    };
+
+    Some(number * multiplier)
+}
+
+/// Report ResourceQuota utilization (status.used vs status.hard) per namespace
+pub fn analyze_quota_usage(quotas: &[Value]) -> Value {
+    let mut namespaces = serde_json::Map::new();
+    let mut near_limit = Vec::new();
+
+    for quota in quotas {
+        let namespace = metadata_str(quota, "namespace").unwrap_or("unknown");
+        let name = metadata_str(quota, "name").unwrap_or("unknown");
+        let status = quota.get("status");
+        let hard = status
+            .and_then(|s| s.get("hard"))
+            .and_then(|h| h.as_object());
+        let used = status
+            .and_then(|s| s.get("used"))
+            .and_then(|u| u.as_object());
+
+        let mut resources = serde_json::Map::new();
+        if let Some(hard) = hard {
+            for (resource, hard_value) in hard {
+                let hard_str = hard_value.as_str().unwrap_or_default();
+                let used_str = used
+                    .and_then(|u| u.get(resource))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("0");
+
+                let utilization = match (parse_quantity(used_str), parse_quantity(hard_str)) {
+                    (Some(used), Some(hard)) if hard > 0.0 => {
+                        Some((used / hard * 1000.0).round() / 10.0)
+                    }
+                    _ => None,
+                };
+
+                if let Some(percent) = utilization
+                    && percent >= QUOTA_NEAR_LIMIT_PERCENT
+                {
+                    near_limit.push(serde_json::json!({
+                        "namespace": namespace,
+                        "quota": name,
+                        "resource": resource,
+                        "used": used_str,
+                        "hard": hard_str,
+                        "utilization_percent": percent
+                    }));
+                }
+
+                resources.insert(
+                    resource.clone(),
+                    serde_json::json!({
+                        "hard": hard_str,
+                        "used": used_str,
+                        "utilization_percent": utilization
+                    }),
+                );
+            }
+        }
+
+        let entry = namespaces
+            .entry(namespace.to_string())
+            .or_insert_with(|| Value::Array(Vec::new()));
+        if let Value::Array(list) = entry {
+            list.push(serde_json::json!({
+                "quota": name,
+                "resources": resources
+            }));
+        }
+    }
+
+    serde_json::json!({
+        "near_limit_threshold_percent": QUOTA_NEAR_LIMIT_PERCENT,
+        "total_quotas": quotas.len(),
+        "namespaces": namespaces,
+        "near_limit": near_limit
+    })
+}
+
+/// Operating system and runtime details of a node, from status.nodeInfo
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeInfo {
+    pub name: String,
+    pub roles: Vec<String>,
+    pub architecture: String,
+    pub operating_system: String,
+    pub os_image: String,
+    pub kernel_version: String,
+    pub container_runtime_version: String,
+    pub kubelet_version: String,
+    pub kube_proxy_version: String,
+    pub gpus: u64,
+}
+
+/// Extended resource advertised by the NVIDIA device plugin
+const NVIDIA_GPU_RESOURCE: &str = "nvidia.com/gpu";
+
+/// Build the node inventory from collected Node objects
+pub fn build_node_inventory(nodes: &[Value]) -> Vec<NodeInfo> {
+    nodes
+        .iter()
+        .map(|node| {
+            let node_info = node.get("status").and_then(|s| s.get("nodeInfo"));
+            let field = |name: &str| {
+                node_info
+                    .and_then(|info| info.get(name))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("unknown")
+                    .to_string()
+            };
+
+            let roles = node
+                .get("metadata")
+                .and_then(|m| m.get("labels"))
+                .and_then(|l| l.as_object())
+                .map(|labels| {
+                    labels
+                        .keys()
+                        .filter_map(|key| key.strip_prefix("node-role.kubernetes.io/"))
+                        .map(|role| role.to_string())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            NodeInfo {
+                name: metadata_str(node, "name").unwrap_or("unknown").to_string(),
+                roles,
+                architecture: field("architecture"),
+                operating_system: field("operatingSystem"),
+                os_image: field("osImage"),
+                kernel_version: field("kernelVersion"),
+                container_runtime_version: field("containerRuntimeVersion"),
+                kubelet_version: field("kubeletVersion"),
+                kube_proxy_version: field("kubeProxyVersion"),
+                gpus: node
+                    .pointer("/status/capacity")
+                    .and_then(|c| c.get(NVIDIA_GPU_RESOURCE))
+                    .and_then(|q| q.as_str())
+                    .and_then(|q| q.parse().ok())
+                    .unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+/// Node inventory report with per-node details and counts by OS image, architecture and kubelet
+pub fn node_inventory_report(nodes: &[NodeInfo]) -> Value {
+    let mut os_images: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut architectures: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut kubelet_versions: BTreeMap<&str, usize> = BTreeMap::new();
+
+    for node in nodes {
+        *os_images.entry(&node.os_image).or_default() += 1;
+        *architectures.entry(&node.architecture).or_default() += 1;
+        *kubelet_versions.entry(&node.kubelet_version).or_default() += 1;
+    }
+
+    serde_json::json!({
+        "total_nodes": nodes.len(),
+        "os_images": os_images,
+        "architectures": architectures,
+        "kubelet_versions": kubelet_versions,
+        "gpu_nodes": nodes.iter().filter(|node| node.gpus > 0).count(),
+        "nodes": nodes
+    })
+}
+
+/// Annotations marking the default StorageClass (GA and legacy beta)
+const DEFAULT_CLASS_ANNOTATIONS: &[&str] = &[
+    "storageclass.kubernetes.io/is-default-class",
+    "storageclass.beta.kubernetes.io/is-default-class",
+];
+
+fn is_default_class(class: &Value) -> bool {
+    DEFAULT_CLASS_ANNOTATIONS.iter().any(|annotation| {
+        class
+            .pointer("/metadata/annotations")
+            .and_then(|a| a.get(*annotation))
+            .and_then(|v| v.as_str())
+            == Some("true")
+    })
+}
+
+/// Report the default StorageClass, flagging zero or several defaults, and the PVCs that
+/// omit storageClassName and so depend on the default when restored
+pub fn analyze_storage_classes(classes: &[Value], claims: &[Value]) -> Value {
+    let default_classes: Vec<&str> = classes
+        .iter()
+        .filter(|class| is_default_class(class))
+        .filter_map(|class| metadata_str(class, "name"))
+        .collect();
+
+    let status = match default_classes.len() {
+        0 => "no_default",
+        1 => "ok",
+        _ => "multiple_defaults",
+    };
+
+    let claims_using_default: Vec<Value> = claims
+        .iter()
+        .filter(|claim| {
+            claim
+                .pointer("/spec/storageClassName")
+                .is_none_or(Value::is_null)
+        })
+        .map(|claim| {
+            serde_json::json!({
+                "namespace": metadata_str(claim, "namespace"),
+                "name": metadata_str(claim, "name")
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "status": status,
+        "default_classes": default_classes,
+        "storage_classes": classes
+            .iter()
+            .map(|class| serde_json::json!({
+                "name": metadata_str(class, "name"),
+                "provisioner": class.get("provisioner"),
+                "reclaim_policy": class.get("reclaimPolicy"),
+                "volume_binding_mode": class.get("volumeBindingMode")
+            }))
+            .collect::<Vec<_>>(),
+        "claims_using_default": claims_using_default
+    })
+}
+
+/// Warning for a StorageClass analysis with more than one default class, which breaks dynamic
+/// provisioning for PVCs that omit storageClassName
+pub fn multiple_default_classes_warning(storageclass_analysis: &Value) -> Option<String> {
+    if storageclass_analysis["status"].as_str() != Some("multiple_defaults") {
+        return None;
+    }
+    let default_classes: Vec<&str> = storageclass_analysis["default_classes"]
+        .as_array()
+        .map(|classes| classes.iter().filter_map(|c| c.as_str()).collect())
+        .unwrap_or_default();
+    Some(format!(
+        "{} StorageClasses are marked default ({}), PVCs without storageClassName may not provision as expected; keep exactly one default, see storageclass-analysis.yaml",
+        default_classes.len(),
+        default_classes.join(", ")
+    ))
+}
+
+/// Provisioners of the two storage backends shipped with SUSE Edge
+const LONGHORN_PROVISIONER: &str = "driver.longhorn.io";
+const LOCAL_PATH_PROVISIONER: &str = "rancher.io/local-path";
+
+/// Report whether Longhorn and local-path StorageClasses coexist, which of them is the default
+/// and which PVCs pin a class explicitly, for the storage section of suse-edge-analysis.yaml
+pub fn analyze_edge_storage(classes: &[Value], claims: &[Value]) -> Value {
+    let names_for = |provisioner: &str| -> Vec<&str> {
+        classes
+            .iter()
+            .filter(|class| class.get("provisioner").and_then(|p| p.as_str()) == Some(provisioner))
+            .filter_map(|class| metadata_str(class, "name"))
+            .collect()
+    };
+    let longhorn_classes = names_for(LONGHORN_PROVISIONER);
+    let local_path_classes = names_for(LOCAL_PATH_PROVISIONER);
+
+    let defaults: Vec<&Value> = classes
+        .iter()
+        .filter(|class| is_default_class(class))
+        .collect();
+    let default_backend = match defaults.as_slice() {
+        [] => "none",
+        [class] => match class.get("provisioner").and_then(|p| p.as_str()) {
+            Some(LONGHORN_PROVISIONER) => "longhorn",
+            Some(LOCAL_PATH_PROVISIONER) => "local-path",
+            _ => "other",
+        },
+        _ => "ambiguous",
+    };
+
+    let (pinned, unpinned): (Vec<&Value>, Vec<&Value>) = claims.iter().partition(|claim| {
+        claim
+            .pointer("/spec/storageClassName")
+            .is_some_and(|c| !c.is_null())
+    });
+
+    let both_present = !longhorn_classes.is_empty() && !local_path_classes.is_empty();
+    let recommendation = match default_backend {
+        "ambiguous" | "none" if both_present => Some(
+            "Both Longhorn and local-path are installed without a single default StorageClass; \
+             mark exactly one as default or set storageClassName on every PVC",
+        ),
+        "ambiguous" => Some("Several StorageClasses are marked default; keep exactly one"),
+        _ => None,
+    };
+
+    serde_json::json!({
+            "longhorn_classes": longhorn_classes,
+            "local_path_classes": local_path_classes,
+            "both_present": both_present,
+            "default_classes": defaults
+                .iter()
+                .filter_map(|class| metadata_str(class, "name"))
+                .collect::<Vec<_>>(),
+            "default_backend": default_backend,
+            "claims_pinning_class": pinned
+                .iter()
+                .map(|claim| serde_json::json!({
+                    "namespace": metadata_str(claim, "namespace"),
+                    "name": metadata_str(claim, "name"),
+                    "storage_class": claim.pointer("/spec/storageClassName")
+                }))
+                .collect::<Vec<_>>(),
+            "claims_using_default": unpinned.len(),
+            "recommendation": recommendation
+    })
+}
+
+/// Current cluster-wide usage summed from NodeMetrics
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceUsage {
+    pub nodes: usize,
+    pub cpu_cores: f64,
+    pub memory_bytes: f64,
+}
+
+/// Sum CPU and memory usage over all NodeMetrics
+pub fn summarize_node_metrics(node_metrics: &[Value]) -> ResourceUsage {
+    let usage = |metrics: &Value, resource: &str| {
+        metrics
+            .pointer(&format!("/usage/{}", resource))
+            .and_then(|q| q.as_str())
+            .and_then(parse_quantity)
+            .unwrap_or_default()
+    };
+
+    ResourceUsage {
+        nodes: node_metrics.len(),
+        cpu_cores: node_metrics.iter().map(|m| usage(m, "cpu")).sum(),
+        memory_bytes: node_metrics.iter().map(|m| usage(m, "memory")).sum(),
+    }
+}
+
+/// Network plugin detected from its DaemonSet in the system namespaces or from its CRDs
+#[derive(Debug, Clone, Serialize)]
+pub struct CniInfo {
+    pub name: String,
+    pub category: String,
+    pub version: String,
+    /// namespace/name of the plugin's DaemonSet, None when only its CRD was found
+    pub daemonset: Option<String>,
+    /// The plugin's CRD, None when it is not installed
+    pub crd: Option<String>,
+}
+
+/// Known CNI DaemonSets and the plugin they belong to
+const CNI_DAEMONSETS: &[(&str, &str)] = &[("cilium", "Cilium"), ("calico-node", "Calico")];
+
+/// CRDs each CNI installs, identifying it even when its DaemonSet runs outside the system
+/// namespaces
+pub const CNI_CRDS: &[(&str, &str)] = &[
+    ("ciliumnetworkpolicies.cilium.io", "Cilium"),
+    ("ippools.crd.projectcalico.org", "Calico"),
+];
+
+/// Detect the CNI from system DaemonSets, taking the version from the first container image,
+/// or else from the installed CRDs (among CNI_CRDS)
+pub fn detect_cni(daemonsets: &[Value], installed_crds: &[&str]) -> Option<CniInfo> {
+    let crd_of = |cni_name: &str| {
+        CNI_CRDS
+            .iter()
+            .find(|(crd, name)| *name == cni_name && installed_crds.contains(crd))
+            .map(|(crd, _)| crd.to_string())
+    };
+
+    let from_daemonset = daemonsets.iter().find_map(|daemonset| {
+        let name = metadata_str(daemonset, "name")?;
+        let (_, cni_name) = CNI_DAEMONSETS.iter().find(|(ds, _)| *ds == name)?;
+
+        let version = daemonset
+            .pointer("/spec/template/spec/containers/0/image")
+            .and_then(|image| image.as_str())
+            .and_then(image_tag)
+            .unwrap_or("unknown");
+
+        Some(CniInfo {
+            name: cni_name.to_string(),
+            category: "Networking".to_string(),
+            version: version.to_string(),
+            daemonset: Some(format!(
+                "{}/{}",
+                metadata_str(daemonset, "namespace").unwrap_or_default(),
+                name
+            )),
+            crd: crd_of(cni_name),
+        })
+    });
+
+    from_daemonset.or_else(|| {
+        let (_, cni_name) = CNI_CRDS
+            .iter()
+            .find(|(crd, _)| installed_crds.contains(crd))?;
+        Some(CniInfo {
+            name: cni_name.to_string(),
+            category: "Networking".to_string(),
+            version: "unknown".to_string(),
+            daemonset: None,
+            crd: crd_of(cni_name),
+        })
+    })
+}
+
+/// An add-on found by the name of its Deployment or DaemonSet
+#[derive(Debug, Clone, Serialize)]
+pub struct DetectedWorkload {
+    pub name: String,
+    pub version: String,
+    pub workload: String,
+}
+
+/// Ingress topology: whether K3s's bundled Traefik is running, and which other ingress
+/// controllers are deployed alongside or instead of it
+#[derive(Debug, Clone, Serialize)]
+pub struct IngressInfo {
+    pub category: String,
+    pub state: String,
+    pub traefik: Option<DetectedWorkload>,
+    pub other_controllers: Vec<DetectedWorkload>,
+}
+
+/// Name fragments of known non-Traefik ingress controller workloads
+const INGRESS_CONTROLLERS: &[(&str, &str)] = &[
+    ("ingress-nginx", "ingress-nginx"),
+    ("nginx-ingress", "NGINX Ingress"),
+    ("haproxy-ingress", "HAProxy Ingress"),
+];
+
+/// Describe a Deployment or DaemonSet as a detected add-on, versioned by its first image tag
+fn detected(resource: &Value, name: &str) -> DetectedWorkload {
+    let version = resource
+        .pointer("/spec/template/spec/containers/0/image")
+        .and_then(|image| image.as_str())
+        .and_then(image_tag)
+        .unwrap_or("unknown");
+    DetectedWorkload {
+        name: name.to_string(),
+        version: version.to_string(),
+        workload: format!(
+            "{} {}/{}",
+            resource
+                .get("kind")
+                .and_then(|k| k.as_str())
+                .unwrap_or("Unknown"),
+            metadata_str(resource, "namespace").unwrap_or_default(),
+            metadata_str(resource, "name").unwrap_or_default()
+        ),
+    }
+}
+
+/// Detect Traefik and other ingress controllers from system Deployments and DaemonSets
+pub fn detect_ingress(deployments: &[Value], daemonsets: &[Value]) -> IngressInfo {
+    let mut traefik = None;
+    let mut other_controllers = Vec::new();
+    for resource in deployments.iter().chain(daemonsets) {
+        let Some(name) = metadata_str(resource, "name") else {
+            continue;
+        };
+        if name == "traefik" {
+            traefik = Some(detected(resource, "Traefik"));
+        } else if let Some((_, controller)) = INGRESS_CONTROLLERS
+            .iter()
+            .find(|(fragment, _)| name.contains(fragment))
+        {
+            other_controllers.push(detected(resource, controller));
+        }
+    }
+
+    let state = match (&traefik, other_controllers.is_empty()) {
+        (Some(_), _) => "traefik",
+        (None, false) => "replaced",
+        (None, true) => "none",
+    };
+
+    IngressInfo {
+        category: "Networking".to_string(),
+        state: state.to_string(),
+        traefik,
+        other_controllers,
+    }
+}
+
+/// NVIDIA GPU support: the GPU operator or a standalone device plugin, and the nodes
+/// advertising nvidia.com/gpu capacity
+#[derive(Debug, Clone, Serialize)]
+pub struct GpuInfo {
+    pub category: String,
+    pub operator: Option<DetectedWorkload>,
+    pub device_plugin: Option<DetectedWorkload>,
+    pub cluster_policy_crd: bool,
+    pub gpu_nodes: usize,
+    pub total_gpus: u64,
+}
+
+/// Summarize Gatekeeper ConstraintTemplates with the constraints enforcing each of them
+pub fn analyze_policy_inventory(templates: &[Value], constraints: &[Value]) -> Value {
+    let template_entries: Vec<Value> = templates
+        .iter()
+        .map(|template| {
+            let kind = template
+                .pointer("/spec/crd/spec/names/kind")
+                .and_then(|k| k.as_str());
+            let enforced: Vec<Value> = constraints
+                .iter()
+                .filter(|constraint| {
+                    kind.is_some() && constraint.get("kind").and_then(|k| k.as_str()) == kind
+                })
+                .map(|constraint| {
+                    serde_json::json!({
+                        "name": metadata_str(constraint, "name"),
+                        "enforcement_action": constraint
+                            .pointer("/spec/enforcementAction")
+                            .and_then(|a| a.as_str())
+                            .unwrap_or("deny"),
+                        "total_violations": constraint.pointer("/status/totalViolations")
+                    })
+                })
+                .collect();
+            serde_json::json!({
+                "name": metadata_str(template, "name"),
+                "kind": kind,
+                "constraints": enforced
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "category": "Security",
+        "engine": "Gatekeeper",
+        "total_templates": templates.len(),
+        "total_constraints": constraints.len(),
+        "templates": template_entries
+    })
+}
+
+/// Summarize K3s/RKE2 HelmChart add-ons and the HelmChartConfigs overriding their values
+pub fn summarize_helm_charts(charts: &[Value], configs: &[Value]) -> Value {
+    let field = |chart: &Value, name: &str| chart.pointer(&format!("/spec/{}", name)).cloned();
+    serde_json::json!({
+        "category": "Lifecycle",
+        "total_helm_charts": charts.len(),
+        "helm_charts": charts
+            .iter()
+            .map(|chart| {
+                let (namespace, name) =
+                    (metadata_str(chart, "namespace"), metadata_str(chart, "name"));
+                serde_json::json!({
+                    "namespace": namespace,
+                    "name": name,
+                    "chart": field(chart, "chart"),
+                    "version": field(chart, "version"),
+                    "repo": field(chart, "repo"),
+                    "target_namespace": field(chart, "targetNamespace"),
+                    "has_config": configs.iter().any(|config| {
+                        metadata_str(config, "namespace") == namespace
+                            && metadata_str(config, "name") == name
+                    })
+                })
+            })
+            .collect::<Vec<_>>(),
+        "helm_chart_configs": configs
+            .iter()
+            .map(|config| serde_json::json!({
+                "namespace": metadata_str(config, "namespace"),
+                "name": metadata_str(config, "name")
+            }))
+            .collect::<Vec<_>>()
+    })
+}
+
+/// Detect the NVIDIA GPU operator and device plugin from system Deployments and DaemonSets,
+/// returning None when neither they, the ClusterPolicy CRD nor GPU capacity are present
+pub fn detect_gpu(
+    deployments: &[Value],
+    daemonsets: &[Value],
+    cluster_policy_crd: bool,
+    nodes: &[NodeInfo],
+) -> Option<GpuInfo> {
+    let find = |resources: &[Value], fragment: &str, name: &str| {
+        resources.iter().find_map(|resource| {
+            metadata_str(resource, "name")
+                .filter(|n| n.contains(fragment))
+                .map(|_| detected(resource, name))
+        })
+    };
+    let operator = find(deployments, "gpu-operator", "NVIDIA GPU Operator");
+    let device_plugin = find(daemonsets, "nvidia-device-plugin", "NVIDIA device plugin");
+    let gpu_nodes = nodes.iter().filter(|node| node.gpus > 0).count();
+
+    if operator.is_none() && device_plugin.is_none() && !cluster_policy_crd && gpu_nodes == 0 {
+        return None;
+    }
+
+    Some(GpuInfo {
+        category: "Hardware".to_string(),
+        operator,
+        device_plugin,
+        cluster_policy_crd,
+        gpu_nodes,
+        total_gpus: nodes.iter().map(|node| node.gpus).sum(),
+    })
+}
+
+/// Tag of a container image reference, ignoring any registry port and digest
+fn image_tag(image: &str) -> Option<&str> {
+    let image = image.split('@').next()?;
+    let (repository, tag) = image.rsplit_once(':')?;
+    (!tag.contains('/') && !repository.is_empty()).then_some(tag)
+}
+
+/// Annotation prefixes and loadBalancerClass fragments that tie a Service to MetalLB or kube-vip
+const METALLB_MARKERS: &[&str] = &["metallb.universe.tf/", "metallb.io/", "metallb"];
+const KUBE_VIP_MARKERS: &[&str] = &["kube-vip.io/", "kube-vip"];
+
+/// Which provider a LoadBalancer Service names through its loadBalancerClass or annotations
+fn marked_load_balancer_provider(service: &Value) -> Option<&'static str> {
+    let class = service
+        .pointer("/spec/loadBalancerClass")
+        .and_then(|c| c.as_str());
+    let annotations = service
+        .pointer("/metadata/annotations")
+        .and_then(|a| a.as_object());
+    let marked = |markers: &[&str]| {
+        class.is_some_and(|class| markers.iter().any(|marker| class.contains(marker)))
+            || annotations.is_some_and(|annotations| {
+                annotations
+                    .keys()
+                    .any(|key| markers.iter().any(|marker| key.starts_with(marker)))
+            })
+    };
+
+    if marked(METALLB_MARKERS) {
+        Some("metallb")
+    } else if marked(KUBE_VIP_MARKERS) {
+        Some("kube-vip")
+    } else {
+        None
+    }
+}
+
+/// Detect MetalLB and kube-vip from system Deployments and DaemonSets and report which
+/// provider likely serves each LoadBalancer Service, flagging clusters that run both as they
+/// can hand out conflicting IPs. This is the networking section of suse-edge-analysis.yaml
+pub fn analyze_load_balancers(
+    deployments: &[Value],
+    daemonsets: &[Value],
+    services: &[Value],
+) -> Value {
+    let mut metallb = None;
+    let mut kube_vip = None;
+    for resource in daemonsets.iter().chain(deployments) {
+        let name = metadata_str(resource, "name").unwrap_or_default();
+        if metallb.is_none()
+            && (name.contains("metallb")
+                || metadata_str(resource, "namespace") == Some("metallb-system"))
+        {
+            metallb = Some(detected(resource, "MetalLB"));
+        } else if kube_vip.is_none() && name.contains("kube-vip") {
+            kube_vip = Some(detected(resource, "kube-vip"));
+        }
+    }
+
+    let conflict = metallb.is_some() && kube_vip.is_some();
+    let load_balancer_services: Vec<Value> = services
+        .iter()
+        .filter(|service| {
+            service.pointer("/spec/type").and_then(|t| t.as_str()) == Some("LoadBalancer")
+        })
+        .map(|service| {
+            let (provider, basis) = match marked_load_balancer_provider(service) {
+                Some(provider) => (provider, "loadBalancerClass or annotation"),
+                None => match (&metallb, &kube_vip) {
+                    (Some(_), None) => ("metallb", "only detected provider"),
+                    (None, Some(_)) => ("kube-vip", "only detected provider"),
+                    (Some(_), Some(_)) => ("ambiguous", "both providers detected"),
+                    (None, None) => ("unknown", "no provider detected"),
+                },
+            };
+            serde_json::json!({
+                "namespace": metadata_str(service, "namespace"),
+                "name": metadata_str(service, "name"),
+                "ingress_ips": service
+                    .pointer("/status/loadBalancer/ingress")
+                    .and_then(|ingress| ingress.as_array())
+                    .map(|ingress| ingress
+                        .iter()
+                        .filter_map(|entry| entry.get("ip").or_else(|| entry.get("hostname")))
+                        .collect::<Vec<_>>())
+                    .unwrap_or_default(),
+                "provider": provider,
+                "basis": basis
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "category": "Networking",
+        "metallb": metallb,
+        "kube_vip": kube_vip,
+        "conflict": conflict,
+        "priority": if conflict { Some("high") } else { None },
+        "recommendation": conflict.then_some(
+            "MetalLB and kube-vip are both running as LoadBalancer providers and may assign \
+             conflicting IPs; keep one of them, or disable kube-vip's Service load balancing \
+             and use it for the control-plane VIP only"
+        ),
+        "load_balancer_services": load_balancer_services
+    })
+}
+
+/// Namespace of the Rancher server and of the agent a downstream cluster runs
+const CATTLE_NAMESPACE: &str = "cattle-system";
+
+/// The management Rancher URL a cattle-cluster-agent connects to, from its CATTLE_SERVER
+/// environment variable or a --server argument
+fn cattle_server_url(agent: &Value) -> Option<String> {
+    let containers = agent
+        .pointer("/spec/template/spec/containers")
+        .and_then(|c| c.as_array())?;
+    for container in containers {
+        let env_url = container
+            .get("env")
+            .and_then(|e| e.as_array())
+            .and_then(|env| {
+                env.iter()
+                    .find(|var| var.get("name").and_then(|n| n.as_str()) == Some("CATTLE_SERVER"))
+            })
+            .and_then(|var| var.get("value"))
+            .and_then(|v| v.as_str());
+        if let Some(url) = env_url {
+            return Some(url.to_string());
+        }
+
+        let args: Vec<&str> = container
+            .get("args")
+            .and_then(|a| a.as_array())
+            .map(|args| args.iter().filter_map(|a| a.as_str()).collect())
+            .unwrap_or_default();
+        for (i, arg) in args.iter().enumerate() {
+            if let Some(url) = arg.strip_prefix("--server=") {
+                return Some(url.to_string());
+            }
+            if *arg == "--server"
+                && let Some(url) = args.get(i + 1)
+            {
+                return Some(url.to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Identify Rancher management: a downstream cluster runs cattle-cluster-agent, whose
+/// configured server URL is its management endpoint, while the management cluster itself runs
+/// the rancher Deployment. This is the management section of suse-edge-analysis.yaml
+pub fn detect_rancher_management(deployments: &[Value]) -> Value {
+    let in_cattle_system = |name: &str| {
+        deployments.iter().find(|deployment| {
+            metadata_str(deployment, "namespace") == Some(CATTLE_NAMESPACE)
+                && metadata_str(deployment, "name") == Some(name)
+        })
+    };
+    let agent = in_cattle_system("cattle-cluster-agent");
+    let server = in_cattle_system("rancher");
+
+    let role = match (server, agent) {
+        (Some(_), _) => "management",
+        (None, Some(_)) => "downstream",
+        (None, None) => "not_managed",
+    };
+
+    serde_json::json!({
+        "category": "Management",
+        "role": role,
+        "rancher_server": server.map(|server| detected(server, "Rancher")),
+        "cluster_agent": agent.map(|agent| detected(agent, "cattle-cluster-agent")),
+        "management_endpoint": agent.and_then(cattle_server_url)
+    })
+}
+
+/// Fragments Edge Image Builder leaves in ConfigMap and Secret names or ConfigMap data:
+/// combustion provisioning scripts and Elemental registration configuration
+const EIB_SIGNATURES: &[&str] = &["combustion", "elemental-register", "edge-image-builder"];
+
+/// Guess whether the cluster was provisioned with SUSE Edge Image Builder from EIB leftovers in
+/// ConfigMap names and data and Secret names (Secret data is not read). This is a heuristic
+/// with low confidence, the infrastructure section of suse-edge-analysis.yaml
+pub fn detect_eib_provisioning(configmaps: &[Value], secrets: &[Value]) -> Value {
+    let mut signals = Vec::new();
+    for (kind, resource) in configmaps
+        .iter()
+        .map(|configmap| ("ConfigMap", configmap))
+        .chain(secrets.iter().map(|secret| ("Secret", secret)))
+    {
+        let name = metadata_str(resource, "name").unwrap_or_default();
+        let data = match kind {
+            "ConfigMap" => resource.get("data").and_then(|d| d.as_object()),
+            _ => None,
+        };
+        let matched = EIB_SIGNATURES.iter().find(|signature| {
+            name.contains(*signature)
+                || data.is_some_and(|data| {
+                    data.iter().any(|(key, value)| {
+                        key.contains(*signature)
+                            || value.as_str().is_some_and(|v| v.contains(*signature))
+                    })
+                })
+        });
+        if let Some(signature) = matched {
+            signals.push(serde_json::json!({
+                "kind": kind,
+                "namespace": metadata_str(resource, "namespace"),
+                "name": name,
+                "signature": signature
+            }));
+        }
+    }
+
+    serde_json::json!({
+        "category": "Infrastructure",
+        "eib_provisioned": !signals.is_empty(),
+        "confidence": "low",
+        "signals": signals,
+        "recommendation": "EIB detection only matches combustion and Elemental registration \
+            leftovers in the collected namespaces; EIB does not require them to persist, so a \
+            negative result does not rule EIB out. Confirm on a node (e.g. /var/lib/combustion \
+            or the image build definition) before relying on it"
+    })
+}
+
+/// Namespace the Rancher Backup Operator is installed into
+const RANCHER_BACKUP_NAMESPACE: &str = "cattle-resources-system";
+
+/// Detect the Rancher Backup Operator from its rancher-backup Deployment and its Backup CRD
+/// (`backups` is None when the CRD is missing), summarizing each Backup's schedule and
+/// retention. Without a scheduled Backup the cluster's Rancher resources are not backed up
+/// regularly. This is the backup section of suse-edge-analysis.yaml
+pub fn detect_rancher_backup(deployments: &[Value], backup_resources: Option<&[Value]>) -> Value {
+    let operator = deployments.iter().find(|deployment| {
+        metadata_str(deployment, "namespace") == Some(RANCHER_BACKUP_NAMESPACE)
+            && metadata_str(deployment, "name") == Some("rancher-backup")
+    });
+
+    let backups: Vec<Value> = backup_resources
+        .unwrap_or_default()
+        .iter()
+        .map(|backup| {
+            serde_json::json!({
+                "name": metadata_str(backup, "name"),
+                "resource_set": backup.pointer("/spec/resourceSetName"),
+                "schedule": backup.pointer("/spec/schedule"),
+                "retention_count": backup.pointer("/spec/retentionCount"),
+                "storage_location": backup
+                    .pointer("/spec/storageLocation")
+                    .map_or("operator default", |_| "custom"),
+                "encrypted": backup
+                    .pointer("/spec/encryptionConfigSecretName")
+                    .is_some_and(|name| name.as_str().is_some_and(|name| !name.is_empty())),
+                "last_snapshot": backup.pointer("/status/lastSnapshotTs")
+            })
+        })
+        .collect();
+    let scheduled_backups = backups
+        .iter()
+        .filter(|backup| backup["schedule"].as_str().is_some_and(|s| !s.is_empty()))
+        .count();
+
+    serde_json::json!({
+        "category": "Lifecycle",
+        "backup_crd": backup_resources.is_some(),
+        "operator": operator.map(|operator| detected(operator, "Rancher Backup Operator")),
+        "scheduled_backups": scheduled_backups,
+        "backups": backups
+    })
+}
+
+/// Name and image fragments of OpenTelemetry Collector Deployments and DaemonSets
+const OTEL_COLLECTOR_MARKERS: &[&str] = &["otel-collector", "opentelemetry-collector"];
+
+/// Exporters configured for an OpenTelemetry Collector, read best-effort from the ConfigMaps
+/// its pod template mounts
+fn otel_exporters(collector: &Value, configmaps: &[Value]) -> (Vec<String>, Vec<String>) {
+    let namespace = metadata_str(collector, "namespace");
+    let mounted: Vec<&str> = collector
+        .pointer("/spec/template/spec/volumes")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|volume| volume.pointer("/configMap/name").and_then(|n| n.as_str()))
+        .collect();
+
+    let mut config_maps = Vec::new();
+    let mut exporters = BTreeSet::new();
+    for configmap in configmaps {
+        let Some(name) = metadata_str(configmap, "name") else {
+            continue;
+        };
+        if metadata_str(configmap, "namespace") != namespace || !mounted.contains(&name) {
+            continue;
+        }
+        config_maps.push(name.to_string());
+        let documents = configmap
+            .get("data")
+            .and_then(|d| d.as_object())
+            .into_iter()
+            .flatten()
+            .filter_map(|(_, content)| content.as_str());
+        for document in documents {
+            if let Ok(config) = serde_yaml::from_str::<Value>(document)
+                && let Some(configured) = config.get("exporters").and_then(|e| e.as_object())
+            {
+                exporters.extend(configured.keys().cloned());
+            }
+        }
+    }
+    (config_maps, exporters.into_iter().collect())
+}
+
+/// Detect OpenTelemetry Collectors from Deployments and DaemonSets, versioned by their image,
+/// with the exporters configured in their mounted ConfigMaps. This is the observability section
+/// of suse-edge-analysis.yaml, which also holds the Prometheus stack
+pub fn detect_opentelemetry(
+    deployments: &[Value],
+    daemonsets: &[Value],
+    configmaps: &[Value],
+    operator_crd: bool,
+) -> Value {
+    let mut seen = BTreeSet::new();
+    let mut collectors = Vec::new();
+    for resource in deployments.iter().chain(daemonsets) {
+        let name = metadata_str(resource, "name").unwrap_or_default();
+        let image = resource
+            .pointer("/spec/template/spec/containers/0/image")
+            .and_then(|i| i.as_str())
+            .unwrap_or_default();
+        if !OTEL_COLLECTOR_MARKERS
+            .iter()
+            .any(|marker| name.contains(marker) || image.contains(marker))
+        {
+            continue;
+        }
+
+        let collector = detected(resource, "OpenTelemetry Collector");
+        // System and collected Deployments overlap when kube-system is collected
+        if !seen.insert(collector.workload.clone()) {
+            continue;
+        }
+        let (config_maps, exporters) = otel_exporters(resource, configmaps);
+        collectors.push(serde_json::json!({
+            "name": collector.name,
+            "version": collector.version,
+            "workload": collector.workload,
+            "config_maps": config_maps,
+            "exporters": exporters
+        }));
+    }
+
+    serde_json::json!({
+        "category": "Observability",
+        "opentelemetry_operator_crd": operator_crd,
+        "collectors": collectors
+    })
+}
+
+/// Namespace of the Harvester HCI components and its bundled KubeVirt
+const HARVESTER_NAMESPACE: &str = "harvester-system";
+
+/// harvesterhci.io and kubevirt.io CRDs that identify Harvester and KubeVirt
+pub const VIRTUALIZATION_CRDS: &[&str] = &[
+    "settings.harvesterhci.io",
+    "virtualmachineimages.harvesterhci.io",
+    "kubevirts.kubevirt.io",
+    "virtualmachines.kubevirt.io",
+];
+
+/// Detect Harvester from its harvesterhci.io CRDs and harvester-system workloads (versioned by
+/// their images), and KubeVirt from its kubevirt.io CRDs or virt-operator. `installed_crds` are
+/// the installed ones among VIRTUALIZATION_CRDS and `longhorn` whether a Longhorn StorageClass
+/// exists. Harvester, KubeVirt and Longhorn together make a hyperconverged setup. This is the
+/// virtualization section of suse-edge-analysis.yaml
+pub fn detect_harvester(
+    deployments: &[Value],
+    daemonsets: &[Value],
+    installed_crds: &[&str],
+    longhorn: bool,
+) -> Value {
+    let crds_in_group = |group: &str| -> Vec<&str> {
+        installed_crds
+            .iter()
+            .copied()
+            .filter(|crd| crd.ends_with(group))
+            .collect()
+    };
+    let harvester_crds = crds_in_group(".harvesterhci.io");
+    let kubevirt_crds = crds_in_group(".kubevirt.io");
+
+    let workloads: Vec<&Value> = deployments
+        .iter()
+        .chain(daemonsets)
+        .filter(|resource| metadata_str(resource, "namespace") == Some(HARVESTER_NAMESPACE))
+        .collect();
+    let named = |name: &str, label: &str| {
+        workloads
+            .iter()
+            .find(|resource| metadata_str(resource, "name") == Some(name))
+            .map(|resource| detected(resource, label))
+    };
+    let harvester = named("harvester", "Harvester");
+    let kubevirt_operator = named("virt-operator", "KubeVirt");
+
+    let harvester_present = harvester.is_some() || !harvester_crds.is_empty();
+    let kubevirt_present = kubevirt_operator.is_some() || !kubevirt_crds.is_empty();
+    let hyperconverged = harvester_present && kubevirt_present && longhorn;
+    let recommendation = hyperconverged.then_some(
+        "Harvester, KubeVirt and Longhorn form a hyperconverged infrastructure: VM disks are \
+         Longhorn volumes, so restore Longhorn and its volumes before the virtual machines and \
+         back VMs up with Harvester VM backups rather than manifests alone",
+    );
+
+    serde_json::json!({
+        "category": "Virtualization",
+        "harvester": harvester,
+        "harvester_crds": harvester_crds,
+        "kubevirt": kubevirt_present,
+        "kubevirt_operator": kubevirt_operator,
+        "kubevirt_crds": kubevirt_crds,
+        "longhorn": longhorn,
+        "workloads": workloads
+            .iter()
+            .filter_map(|resource| {
+                metadata_str(resource, "name").map(|name| detected(resource, name))
+            })
+            .collect::<Vec<_>>(),
+        "hyperconverged": hyperconverged,
+        "recommendation": recommendation
+    })
+}
+
+/// Namespace of the Rancher Monitoring (kube-prometheus-stack) chart
+const MONITORING_NAMESPACE: &str = "cattle-monitoring-system";
+
+/// Detect Rancher Monitoring from its operator, Prometheus and Grafana workloads in
+/// cattle-monitoring-system, versioned by their images, and the monitoring.coreos.com
+/// Prometheus and Alertmanager CRDs. This is the prometheus part of the observability section
+/// of suse-edge-analysis.yaml
+pub fn detect_prometheus_stack(
+    deployments: &[Value],
+    statefulsets: &[Value],
+    prometheus_crd: bool,
+    alertmanager_crd: bool,
+) -> Value {
+    let find = |resources: &[Value], matches: fn(&str) -> bool, name: &str| {
+        resources.iter().find_map(|resource| {
+            (metadata_str(resource, "namespace") == Some(MONITORING_NAMESPACE)
+                && metadata_str(resource, "name").is_some_and(matches))
+            .then(|| detected(resource, name))
+        })
+    };
+    let operator = find(
+        deployments,
+        |name| name.ends_with("monitoring-operator"),
+        "Rancher Monitoring Operator",
+    );
+    // The Prometheus operator names the StatefulSets it creates prometheus-<Prometheus name>
+    let prometheus = find(
+        statefulsets,
+        |name| name.starts_with("prometheus-"),
+        "Prometheus",
+    );
+    let grafana = find(deployments, |name| name.ends_with("grafana"), "Grafana");
+
+    serde_json::json!({
+        "category": "Observability",
+        "rancher_monitoring": operator.is_some(),
+        "prometheus_crd": prometheus_crd,
+        "alertmanager_crd": alertmanager_crd,
+        "operator": operator,
+        "prometheus": prometheus,
+        "grafana": grafana
+    })
+}
+
+/// Report PVCs stuck in Pending/Lost and PVs that have no claimRef
+pub fn analyze_storage(claims: &[Value], volumes: &[Value]) -> Value {
+    let mut problem_claims = Vec::new();
+    for claim in claims {
+        let phase = claim
+            .get("status")
+            .and_then(|s| s.get("phase"))
+            .and_then(|p| p.as_str())
+            .unwrap_or("Unknown");
+        if phase != "Pending" && phase != "Lost" {
+            continue;
+        }
+
+        let spec = claim.get("spec");
+        problem_claims.push(serde_json::json!({
+            "namespace": metadata_str(claim, "namespace"),
+            "name": metadata_str(claim, "name"),
+            "phase": phase,
+            "storage_class": spec.and_then(|s| s.get("storageClassName")),
+            "requested": spec
+                .and_then(|s| s.get("resources"))
+                .and_then(|r| r.get("requests"))
+                .and_then(|r| r.get("storage"))
+        }));
+    }
+
+    let mut unbound_volumes = Vec::new();
+    for volume in volumes {
+        let spec = volume.get("spec");
+        if spec.and_then(|s| s.get("claimRef")).is_some() {
+            continue;
+        }
+
+        unbound_volumes.push(serde_json::json!({
+            "name": metadata_str(volume, "name"),
+            "phase": volume.get("status").and_then(|s| s.get("phase")),
+            "storage_class": spec.and_then(|s| s.get("storageClassName")),
+            "capacity": spec
+                .and_then(|s| s.get("capacity"))
+                .and_then(|c| c.get("storage")),
+            "reclaim_policy": spec.and_then(|s| s.get("persistentVolumeReclaimPolicy"))
+        }));
+    }
+
+    serde_json::json!({
+        "total_claims": claims.len(),
+        "total_volumes": volumes.len(),
+        "pending_or_lost_claims": problem_claims,
+        "unbound_volumes": unbound_volumes
+    })
+}
+
+/// Record each PV's reclaim policy, capacity, storage class and the PVC its claimRef binds it
+/// to, so bindings can be re-established after a restore
+pub fn analyze_pv_bindings(volumes: &[Value]) -> Value {
+    let mut by_reclaim_policy: BTreeMap<String, usize> = BTreeMap::new();
+    let bindings: Vec<Value> = volumes
+        .iter()
+        .map(|volume| {
+            let spec = volume.get("spec");
+            let reclaim_policy = spec
+                .and_then(|s| s.get("persistentVolumeReclaimPolicy"))
+                .and_then(|p| p.as_str())
+                .unwrap_or("Unknown");
+            *by_reclaim_policy
+                .entry(reclaim_policy.to_string())
+                .or_default() += 1;
+
+            let claim_ref = spec.and_then(|s| s.get("claimRef"));
+            serde_json::json!({
+                "name": metadata_str(volume, "name"),
+                "reclaim_policy": reclaim_policy,
+                "capacity": spec
+                    .and_then(|s| s.get("capacity"))
+                    .and_then(|c| c.get("storage")),
+                "storage_class": spec.and_then(|s| s.get("storageClassName")),
+                "access_modes": spec.and_then(|s| s.get("accessModes")),
+                "phase": volume.get("status").and_then(|s| s.get("phase")),
+                "bound_claim": claim_ref.map(|claim| serde_json::json!({
+                    "namespace": claim.get("namespace"),
+                    "name": claim.get("name"),
+                    "uid": claim.get("uid")
+                }))
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "total_volumes": volumes.len(),
+        "bound_volumes": bindings.iter().filter(|b| !b["bound_claim"].is_null()).count(),
+        "by_reclaim_policy": by_reclaim_policy,
+        "volumes": bindings
+    })
+}
+
+/// Pod Security admission modes configured through namespace labels
+const POD_SECURITY_MODES: &[&str] = &["enforce", "audit", "warn"];
+
+/// Report the Pod Security Standards level per namespace, flagging namespaces that enforce
+/// `privileged` or carry no pod-security labels at all
+pub fn analyze_pod_security(namespaces: &[Value], collected: &[String]) -> Value {
+    let mut levels = Vec::new();
+    let mut privileged = Vec::new();
+    let mut unlabeled = Vec::new();
+
+    for namespace in namespaces {
+        let Some(name) = metadata_str(namespace, "name") else {
+            continue;
+        };
+        if !collected.iter().any(|ns| ns == name) {
+            continue;
+        }
+
+        let label = |key: String| {
+            namespace
+                .pointer("/metadata/labels")
+                .and_then(|l| l.get(&key))
+                .and_then(|v| v.as_str())
+        };
+        let mut entry = serde_json::Map::new();
+        entry.insert("namespace".to_string(), name.into());
+        for mode in POD_SECURITY_MODES {
+            let level = label(format!("pod-security.kubernetes.io/{}", mode));
+            let version = label(format!("pod-security.kubernetes.io/{}-version", mode));
+            entry.insert(
+                mode.to_string(),
+                serde_json::json!({ "level": level, "version": version }),
+            );
+        }
+
+        if POD_SECURITY_MODES
+            .iter()
+            .all(|mode| label(format!("pod-security.kubernetes.io/{}", mode)).is_none())
+        {
+            unlabeled.push(name);
+        }
+        if label("pod-security.kubernetes.io/enforce".to_string()) == Some("privileged") {
+            privileged.push(name);
+        }
+
+        levels.push(Value::Object(entry));
+    }
+
+    serde_json::json!({
+        "total_namespaces": levels.len(),
+        "privileged_namespaces": privileged,
+        "unlabeled_namespaces": unlabeled,
+        "namespaces": levels
+    })
+}
+
+/// Whether a PDB's minAvailable/maxUnavailable leave no room for a voluntary eviction of its
+/// replicas
+fn blocks_all_disruptions(pdb: &Value, replicas: u64) -> bool {
+    let min_available = pdb.pointer("/spec/minAvailable");
+    let max_unavailable = pdb.pointer("/spec/maxUnavailable");
+
+    match (min_available, max_unavailable) {
+        (_, Some(max)) => max.as_u64() == Some(0) || max.as_str() == Some("0%"),
+        (Some(min), None) => match (min.as_u64(), min.as_str()) {
+            (Some(min), _) => replicas > 0 && min >= replicas,
+            (_, Some(percent)) => percent == "100%",
+            _ => false,
+        },
+        (None, None) => false,
+    }
+}
+
+/// List every resource that carries finalizers, and which ones, so a restore or deletion that
+/// hangs can be traced to the controller expected to clear them
+pub fn analyze_finalizers<'a>(resources: impl IntoIterator<Item = &'a Value>) -> Value {
+    let mut by_finalizer: BTreeMap<String, usize> = BTreeMap::new();
+    let mut with_finalizers = Vec::new();
+    for resource in resources {
+        let finalizers: Vec<&str> = resource
+            .pointer("/metadata/finalizers")
+            .and_then(|f| f.as_array())
+            .map(|f| f.iter().filter_map(|f| f.as_str()).collect())
+            .unwrap_or_default();
+        if finalizers.is_empty() {
+            continue;
+        }
+
+        for finalizer in &finalizers {
+            *by_finalizer.entry(finalizer.to_string()).or_default() += 1;
+        }
+        with_finalizers.push(serde_json::json!({
+            "kind": resource.get("kind").and_then(|k| k.as_str()),
+            "namespace": metadata_str(resource, "namespace"),
+            "name": metadata_str(resource, "name"),
+            "finalizers": finalizers,
+            // Already deleted and waiting on its finalizers
+            "deleting": metadata_str(resource, "deletionTimestamp").is_some()
+        }));
+    }
+
+    serde_json::json!({
+        "total_resources_with_finalizers": with_finalizers.len(),
+        "by_finalizer": by_finalizer,
+        "resources": with_finalizers
+    })
+}
+
+/// Distinct values listed per label or annotation key in label-usage.yaml; high-cardinality
+/// keys only report their distinct count beyond this
+const MAX_LISTED_VALUES: usize = 20;
+/// Longer values (e.g. last-applied-configuration annotations) are counted but not listed
+const MAX_LISTED_VALUE_LEN: usize = 100;
+
+/// Tally how many resources carry each key of one metadata map (labels or annotations), with
+/// the distinct values seen
+fn tally_metadata_keys<'a>(resources: &[&'a Value], field: &str) -> Value {
+    let mut keys: BTreeMap<&'a str, (usize, BTreeSet<&'a str>)> = BTreeMap::new();
+    for resource in resources {
+        let Some(entries) = resource
+            .get("metadata")
+            .and_then(|m| m.get(field))
+            .and_then(|e| e.as_object())
+        else {
+            continue;
+        };
+        for (key, value) in entries {
+            let (count, values) = keys.entry(key.as_str()).or_default();
+            *count += 1;
+            values.insert(value.as_str().unwrap_or_default());
+        }
+    }
+
+    keys.into_iter()
+        .map(|(key, (count, values))| {
+            let listed: Vec<&str> = values
+                .iter()
+                .copied()
+                .filter(|value| value.len() <= MAX_LISTED_VALUE_LEN)
+                .take(MAX_LISTED_VALUES)
+                .collect();
+            let usage = serde_json::json!({
+                "resources": count,
+                "distinct_values": values.len(),
+                "values_truncated": listed.len() < values.len(),
+                "values": listed
+            });
+            (key.to_string(), usage)
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// Count label and annotation keys across the collected resources with their distinct values
+/// (capped), to check which labeling conventions are actually followed
+pub fn analyze_label_usage<'a>(resources: impl IntoIterator<Item = &'a Value>) -> Value {
+    let resources: Vec<&Value> = resources.into_iter().collect();
+    serde_json::json!({
+        "total_resources": resources.len(),
+        "max_listed_values": MAX_LISTED_VALUES,
+        "labels": tally_metadata_keys(&resources, "labels"),
+        "annotations": tally_metadata_keys(&resources, "annotations")
+    })
+}
+
+/// Flag PodDisruptionBudgets that can block node drains: those whose selector matches no
+/// collected Pod, and those whose budget allows zero disruptions for their replicas. Replicas
+/// come from the matching Deployments, or the matching Pods when no Deployment matches
+pub fn analyze_pdbs(pdbs: &[Value], pods: &[Value], deployments: &[Value]) -> Value {
+    let no_labels = serde_json::Map::new();
+
+    let mut no_matching_pods = Vec::new();
+    let mut zero_disruption_budget = Vec::new();
+    for pdb in pdbs {
+        let namespace = metadata_str(pdb, "namespace");
+        // A policy/v1 PDB without a selector selects nothing, an empty one selects every Pod
+        let selects = |resource: &Value, pointer: &str| {
+            let labels = resource
+                .pointer(pointer)
+                .and_then(|l| l.as_object())
+                .unwrap_or(&no_labels);
+            metadata_str(resource, "namespace") == namespace
+                && pdb
+                    .pointer("/spec/selector")
+                    .is_some_and(|selector| selector_matches(selector, labels))
+        };
+
+        let matching_pods = pods
+            .iter()
+            .filter(|pod| selects(pod, "/metadata/labels"))
+            .count();
+        let matching_deployments: Vec<&Value> = deployments
+            .iter()
+            .filter(|deployment| selects(deployment, "/spec/template/metadata/labels"))
+            .collect();
+        let replicas = if matching_deployments.is_empty() {
+            matching_pods as u64
+        } else {
+            matching_deployments
+                .iter()
+                .map(|deployment| {
+                    deployment
+                        .pointer("/spec/replicas")
+                        .and_then(|r| r.as_u64())
+                        .unwrap_or(1)
+                })
+                .sum()
+        };
+
+        let entry = serde_json::json!({
+            "namespace": namespace,
+            "name": metadata_str(pdb, "name"),
+            "min_available": pdb.pointer("/spec/minAvailable"),
+            "max_unavailable": pdb.pointer("/spec/maxUnavailable"),
+            "matching_pods": matching_pods,
+            "replicas": replicas,
+            "disruptions_allowed": pdb.pointer("/status/disruptionsAllowed")
+        });
+        if matching_pods == 0 {
+            no_matching_pods.push(entry);
+        } else if blocks_all_disruptions(pdb, replicas) {
+            zero_disruption_budget.push(entry);
+        }
+    }
+
+    serde_json::json!({
+        "total_pdbs": pdbs.len(),
+        "no_matching_pods": no_matching_pods,
+        "zero_disruption_budget": zero_disruption_budget
+    })
+}
+
+/// Whether a label selector (matchLabels and matchExpressions) matches a set of labels
+fn selector_matches(selector: &Value, labels: &serde_json::Map<String, Value>) -> bool {
+    let match_labels_ok = selector
+        .get("matchLabels")
+        .and_then(|m| m.as_object())
+        .into_iter()
+        .flatten()
+        .all(|(key, value)| labels.get(key) == Some(value));
+
+    let match_expressions_ok = selector
+        .get("matchExpressions")
+        .and_then(|m| m.as_array())
+        .into_iter()
+        .flatten()
+        .all(|expression| {
+            let key = expression
+                .get("key")
+                .and_then(|k| k.as_str())
+                .unwrap_or_default();
+            let values: Vec<&str> = expression
+                .get("values")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|v| v.as_str())
+                .collect();
+            let label = labels.get(key).and_then(|v| v.as_str());
+            match expression.get("operator").and_then(|o| o.as_str()) {
+                Some("In") => label.is_some_and(|l| values.contains(&l)),
+                Some("NotIn") => !label.is_some_and(|l| values.contains(&l)),
+                Some("Exists") => label.is_some(),
+                Some("DoesNotExist") => label.is_none(),
+                _ => false,
+            }
+        });
+
+    match_labels_ok && match_expressions_ok
+}
+
+/// Subjects that cover every (authenticated or anonymous) user or every ServiceAccount
+const BROAD_SUBJECTS: &[&str] = &[
+    "system:authenticated",
+    "system:unauthenticated",
+    "system:anonymous",
+    "system:serviceaccounts",
+];
+
+/// Verbs that read Secret contents
+const SECRET_READ_VERBS: &[&str] = &["get", "list", "watch", "*"];
+
+fn rule_strings<'a>(rule: &'a Value, field: &str) -> Vec<&'a str> {
+    rule.get(field)
+        .and_then(|v| v.as_array())
+        .map(|values| values.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default()
+}
+
+fn role_rules(role: &Value) -> &[Value] {
+    role.get("rules")
+        .and_then(|r| r.as_array())
+        .map_or(&[], Vec::as_slice)
+}
+
+/// Whether a rule lets its holder read Secrets in the core API group
+fn grants_secret_read(rule: &Value) -> bool {
+    rule_strings(rule, "apiGroups")
+        .iter()
+        .any(|group| group.is_empty() || *group == "*")
+        && rule_strings(rule, "resources")
+            .iter()
+            .any(|resource| *resource == "secrets" || *resource == "*")
+        && rule_strings(rule, "verbs")
+            .iter()
+            .any(|verb| SECRET_READ_VERBS.contains(verb))
+}
+
+/// Whether a binding subject is one of the broad groups, including all ServiceAccounts of a
+/// namespace
+fn is_broad_subject(subject: &Value) -> bool {
+    let name = subject
+        .get("name")
+        .and_then(|n| n.as_str())
+        .unwrap_or_default();
+    BROAD_SUBJECTS.contains(&name) || name.starts_with("system:serviceaccounts:")
+}
+
+/// Whether a binding subject is a Kubernetes component rather than a user or workload
+fn is_system_subject(subject: &Value) -> bool {
+    let field = |name: &str| subject.get(name).and_then(|v| v.as_str());
+    match field("kind") {
+        Some("ServiceAccount") => field("namespace") == Some("kube-system"),
+        _ => field("name").is_some_and(|name| name.starts_with("system:")),
+    }
+}
+
+/// Flag dangerous RBAC in rbac-warnings.yaml: rules granting every verb on every resource
+/// outside the built-in cluster-admin and system: roles, roles that read Secrets bound to
+/// broad subjects, and ClusterRoleBindings of cluster-admin to non-system subjects
+pub fn analyze_rbac_warnings(
+    cluster_roles: &[Value],
+    roles: &[Value],
+    cluster_role_bindings: &[Value],
+    role_bindings: &[Value],
+) -> Value {
+    let mut wildcard_rules = Vec::new();
+    for role in cluster_roles.iter().chain(roles) {
+        let name = metadata_str(role, "name").unwrap_or("unknown");
+        if name == "cluster-admin" || name.starts_with("system:") {
+            continue;
+        }
+        for rule in role_rules(role) {
+            if rule_strings(rule, "verbs").contains(&"*")
+                && rule_strings(rule, "resources").contains(&"*")
+            {
+                wildcard_rules.push(serde_json::json!({
+                    "kind": role.get("kind"),
+                    "namespace": metadata_str(role, "namespace"),
+                    "name": name,
+                    "api_groups": rule.get("apiGroups")
+                }));
+            }
+        }
+    }
+
+    let mut broad_secret_access = Vec::new();
+    for binding in cluster_role_bindings.iter().chain(role_bindings) {
+        let namespace = metadata_str(binding, "namespace");
+        let role_kind = binding.pointer("/roleRef/kind").and_then(|k| k.as_str());
+        let role_name = binding.pointer("/roleRef/name").and_then(|n| n.as_str());
+        let role = match role_kind {
+            Some("ClusterRole") => cluster_roles
+                .iter()
+                .find(|role| metadata_str(role, "name") == role_name),
+            _ => roles.iter().find(|role| {
+                metadata_str(role, "name") == role_name
+                    && metadata_str(role, "namespace") == namespace
+            }),
+        };
+        if !role.is_some_and(|role| role_rules(role).iter().any(grants_secret_read)) {
+            continue;
+        }
+
+        let subjects: Vec<&Value> = binding
+            .get("subjects")
+            .and_then(|s| s.as_array())
+            .map(|subjects| subjects.iter().filter(|s| is_broad_subject(s)).collect())
+            .unwrap_or_default();
+        if !subjects.is_empty() {
+            broad_secret_access.push(serde_json::json!({
+                "binding_kind": binding.get("kind"),
+                "namespace": namespace,
+                "binding": metadata_str(binding, "name"),
+                "role_ref": binding.get("roleRef"),
+                "broad_subjects": subjects
+            }));
+        }
+    }
+
+    let cluster_admin_bindings: Vec<Value> = cluster_role_bindings
+        .iter()
+        .filter(|binding| {
+            binding.pointer("/roleRef/kind").and_then(|k| k.as_str()) == Some("ClusterRole")
+                && binding.pointer("/roleRef/name").and_then(|n| n.as_str())
+                    == Some("cluster-admin")
+        })
+        .filter_map(|binding| {
+            let subjects: Vec<&Value> = binding
+                .get("subjects")
+                .and_then(|s| s.as_array())?
+                .iter()
+                .filter(|subject| !is_system_subject(subject))
+                .collect();
+            (!subjects.is_empty()).then(|| {
+                serde_json::json!({
+                    "binding": metadata_str(binding, "name"),
+                    "subjects": subjects
+                })
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "total_warnings": wildcard_rules.len() + broad_secret_access.len() + cluster_admin_bindings.len(),
+        "wildcard_rules": wildcard_rules,
+        "broad_secret_access": broad_secret_access,
+        "cluster_admin_bindings": cluster_admin_bindings
+    })
+}
+
+/// List aggregated ClusterRoles and the labeled ClusterRoles whose rules they pull in
+pub fn analyze_rbac_aggregation(cluster_roles: &[Value]) -> Value {
+    let no_labels = serde_json::Map::new();
+
+    let mut aggregated = Vec::new();
+    for role in cluster_roles {
+        let Some(selectors) = role
+            .pointer("/aggregationRule/clusterRoleSelectors")
+            .and_then(|s| s.as_array())
+        else {
+            continue;
+        };
+        let name = metadata_str(role, "name").unwrap_or("unknown");
+
+        let sources: Vec<&str> = cluster_roles
+            .iter()
+            .filter(|candidate| metadata_str(candidate, "name") != Some(name))
+            .filter(|candidate| {
+                let labels = candidate
+                    .pointer("/metadata/labels")
+                    .and_then(|l| l.as_object())
+                    .unwrap_or(&no_labels);
+                selectors
+                    .iter()
+                    .any(|selector| selector_matches(selector, labels))
+            })
+            .filter_map(|candidate| metadata_str(candidate, "name"))
+            .collect();
+
+        aggregated.push(serde_json::json!({
+            "name": name,
+            "cluster_role_selectors": selectors,
+            "aggregated_from": sources,
+            "collected_rule_count": role.get("rules").and_then(|r| r.as_array()).map_or(0, |r| r.len())
+        }));
+    }
+
+    serde_json::json!({
+        "total_cluster_roles": cluster_roles.len(),
+        "total_aggregated_cluster_roles": aggregated.len(),
+        "aggregated_cluster_roles": aggregated
+    })
+}
+
+/// Pod spec of a Pod, or the pod template spec of a workload controller
+fn pod_spec(workload: &Value) -> Option<&Value> {
+    if workload.get("kind").and_then(|k| k.as_str()) == Some("Pod") {
+        workload.get("spec")
+    } else {
+        workload.pointer("/spec/template/spec")
+    }
+}
+
+/// Container images referenced by a workload, including init containers
+fn workload_images(workload: &Value) -> Vec<&str> {
+    let Some(pod_spec) = pod_spec(workload) else {
+        return Vec::new();
+    };
+
+    ["initContainers", "containers"]
+        .iter()
+        .filter_map(|field| pod_spec.get(*field).and_then(|c| c.as_array()))
+        .flatten()
+        .filter_map(|container| container.get("image").and_then(|i| i.as_str()))
+        .collect()
+}
+
+/// Registry host of an image reference. Like Docker, the first path component is only a
+/// registry if it looks like a host (has a dot or port, or is localhost).
+fn image_registry(image: &str) -> &str {
+    match image.split_once('/') {
+        Some((first, _)) if first.contains('.') || first.contains(':') || first == "localhost" => {
+            first
+        }
+        _ => "docker.io",
+    }
+}
+
+/// Count distinct images per registry host, to size the mirroring needed for air-gapped
+/// installs
+pub fn registry_breakdown(workloads: &[Value]) -> Value {
+    let images: BTreeSet<&str> = workloads.iter().flat_map(workload_images).collect();
+
+    let mut registries: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for image in &images {
+        registries
+            .entry(image_registry(image))
+            .or_default()
+            .push(image);
+    }
+
+    let registries: BTreeMap<&str, Value> = registries
+        .into_iter()
+        .map(|(registry, images)| {
+            (
+                registry,
+                serde_json::json!({ "image_count": images.len(), "images": images }),
+            )
+        })
+        .collect();
+
+    serde_json::json!({
+        "total_images": images.len(),
+        "total_registries": registries.len(),
+        "registries": registries
+    })
+}
+
+/// Name in a Secret/ConfigMap reference, unless the reference is optional (and so cannot
+/// break a restore when missing)
+fn name_of<'a>(field: &str, reference: &'a Value) -> Option<&'a str> {
+    if reference.get("optional").and_then(|o| o.as_bool()) == Some(true) {
+        return None;
+    }
+    reference.get(field).and_then(|n| n.as_str())
+}
+
+/// Secret and ConfigMap names referenced by a pod spec, as (kind, name, field)
+fn pod_spec_references(pod_spec: &Value) -> Vec<(&'static str, &str, &'static str)> {
+    let mut references = Vec::new();
+
+    for secret in pod_spec
+        .get("imagePullSecrets")
+        .and_then(|s| s.as_array())
+        .into_iter()
+        .flatten()
+    {
+        if let Some(name) = name_of("name", secret) {
+            references.push(("Secret", name, "imagePullSecrets"));
+        }
+    }
+
+    for container_field in ["initContainers", "containers"] {
+        for container in pod_spec
+            .get(container_field)
+            .and_then(|c| c.as_array())
+            .into_iter()
+            .flatten()
+        {
+            for source in container
+                .get("envFrom")
+                .and_then(|e| e.as_array())
+                .into_iter()
+                .flatten()
+            {
+                if let Some(name) = source.get("configMapRef").and_then(|r| name_of("name", r)) {
+                    references.push(("ConfigMap", name, "envFrom"));
+                }
+                if let Some(name) = source.get("secretRef").and_then(|r| name_of("name", r)) {
+                    references.push(("Secret", name, "envFrom"));
+                }
+            }
+        }
+    }
+
+    for volume in pod_spec
+        .get("volumes")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+    {
+        if let Some(name) = volume.get("configMap").and_then(|r| name_of("name", r)) {
+            references.push(("ConfigMap", name, "volumes"));
+        }
+        if let Some(name) = volume.get("secret").and_then(|r| name_of("secretName", r)) {
+            references.push(("Secret", name, "volumes"));
+        }
+        for source in volume
+            .pointer("/projected/sources")
+            .and_then(|s| s.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if let Some(name) = source.get("configMap").and_then(|r| name_of("name", r)) {
+                references.push(("ConfigMap", name, "volumes"));
+            }
+            if let Some(name) = source.get("secret").and_then(|r| name_of("name", r)) {
+                references.push(("Secret", name, "volumes"));
+            }
+        }
+    }
+
+    references
+}
+
+/// Secret names referenced by an Ingress for TLS termination
+pub fn ingress_tls_references(ingress: &Value) -> Vec<(&'static str, &str, &'static str)> {
+    ingress
+        .pointer("/spec/tls")
+        .and_then(|t| t.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|tls| tls.get("secretName").and_then(|n| n.as_str()))
+        .map(|name| ("Secret", name, "spec.tls"))
+        .collect()
+}
+
+/// Report Secrets and ConfigMaps referenced by Pods, Deployments and Ingresses that were not
+/// collected in the same namespace, and Services backing admission webhooks that were not
+/// collected
+pub fn find_missing_references(
+    workloads: &[Value],
+    secrets: &[Value],
+    configmaps: &[Value],
+    webhook_configurations: &[Value],
+    services: &[Value],
+) -> Value {
+    let names = |resources: &[Value]| -> BTreeSet<(String, String)> {
+        resources
+            .iter()
+            .filter_map(|r| {
+                Some((
+                    metadata_str(r, "namespace")?.to_string(),
+                    metadata_str(r, "name")?.to_string(),
+                ))
+            })
+            .collect()
+    };
+    let collected_secrets = names(secrets);
+    let collected_configmaps = names(configmaps);
+    let collected_services = names(services);
+
+    let mut missing = Vec::new();
+    for workload in workloads {
+        let kind = workload
+            .get("kind")
+            .and_then(|k| k.as_str())
+            .unwrap_or("Unknown");
+        let Some(namespace) = metadata_str(workload, "namespace") else {
+            continue;
+        };
+        let references = match (kind, pod_spec(workload)) {
+            ("Ingress", _) => ingress_tls_references(workload),
+            (_, Some(pod_spec)) => pod_spec_references(pod_spec),
+            (_, None) => continue,
+        };
+
+        for (referenced_kind, referenced_name, field) in references {
+            let collected = match referenced_kind {
+                "Secret" => &collected_secrets,
+                _ => &collected_configmaps,
+            };
+            if !collected.contains(&(namespace.to_string(), referenced_name.to_string())) {
+                missing.push(serde_json::json!({
+                    "namespace": namespace,
+                    "kind": kind,
+                    "name": metadata_str(workload, "name").unwrap_or("unknown"),
+                    "field": field,
+                    "referenced_kind": referenced_kind,
+                    "referenced_name": referenced_name
+                }));
+            }
+        }
+    }
+
+    // Webhook configurations are cluster-scoped, so the namespace is the Service's own
+    for configuration in webhook_configurations {
+        for webhook in configuration
+            .get("webhooks")
+            .and_then(|w| w.as_array())
+            .into_iter()
+            .flatten()
+        {
+            let Some(service) = webhook.pointer("/clientConfig/service") else {
+                continue;
+            };
+            let (Some(namespace), Some(name)) = (
+                service.get("namespace").and_then(|n| n.as_str()),
+                service.get("name").and_then(|n| n.as_str()),
+            ) else {
+                continue;
+            };
+            if !collected_services.contains(&(namespace.to_string(), name.to_string())) {
+                missing.push(serde_json::json!({
+                    "namespace": namespace,
+                    "kind": configuration.get("kind").and_then(|k| k.as_str()).unwrap_or("Unknown"),
+                    "name": metadata_str(configuration, "name").unwrap_or("unknown"),
+                    "webhook": webhook.get("name"),
+                    "field": "webhooks.clientConfig.service",
+                    "referenced_kind": "Service",
+                    "referenced_name": name
+                }));
+            }
+        }
+    }
+
+    serde_json::json!({
+        "total_missing": missing.len(),
+        "missing_references": missing
+    })
+}
+
+/// Restore-risk severities, most blocking first
+const RESTORE_RISK_SEVERITIES: &[&str] = &["critical", "high", "low"];
+
+/// Rank admission webhooks and APIServices whose backing Service was not collected by how
+/// badly they block a restore: a failing-closed webhook rejects every request it matches until
+/// its Service runs, an unavailable APIService stalls discovery and namespace deletion, and a
+/// failing-open webhook only adds its timeout to matching requests
+pub fn analyze_restore_risks(
+    webhook_configurations: &[Value],
+    apiservices: &[Value],
+    services: &[Value],
+    collected_namespaces: &[String],
+) -> Value {
+    let mut risks = Vec::new();
+    let mut assess = |service: &Value, mut entry: Value| {
+        let (Some(namespace), Some(name)) = (
+            service.get("namespace").and_then(|n| n.as_str()),
+            service.get("name").and_then(|n| n.as_str()),
+        ) else {
+            return;
+        };
+        let collected = services.iter().any(|candidate| {
+            metadata_str(candidate, "namespace") == Some(namespace)
+                && metadata_str(candidate, "name") == Some(name)
+        });
+        let reason = if collected {
+            return;
+        } else if collected_namespaces.iter().any(|ns| ns == namespace) {
+            "service_missing"
+        } else {
+            "namespace_not_collected"
+        };
+
+        entry["service"] = format!("{}/{}", namespace, name).into();
+        entry["reason"] = reason.into();
+        risks.push(entry);
+    };
+
+    for configuration in webhook_configurations {
+        let kind = configuration
+            .get("kind")
+            .and_then(|k| k.as_str())
+            .unwrap_or("Unknown");
+        for webhook in configuration
+            .get("webhooks")
+            .and_then(|w| w.as_array())
+            .into_iter()
+            .flatten()
+        {
+            let Some(service) = webhook.pointer("/clientConfig/service") else {
+                continue;
+            };
+            // admissionregistration/v1 defaults failurePolicy to Fail
+            let failure_policy = webhook
+                .get("failurePolicy")
+                .and_then(|p| p.as_str())
+                .unwrap_or("Fail");
+            let (severity, impact) = if failure_policy == "Fail" {
+                (
+                    "critical",
+                    "requests matching this webhook are rejected until its Service runs; restore the Service first or remove the webhook during the restore",
+                )
+            } else {
+                (
+                    "low",
+                    "requests matching this webhook wait for its timeout while the Service is down",
+                )
+            };
+            assess(
+                service,
+                serde_json::json!({
+                    "severity": severity,
+                    "kind": kind,
+                    "name": metadata_str(configuration, "name"),
+                    "webhook": webhook.get("name"),
+                    "failure_policy": failure_policy,
+                    "impact": impact
+                }),
+            );
+        }
+    }
+
+    // Local APIServices (served by kube-apiserver itself) have no service
+    for apiservice in apiservices {
+        let Some(service) = apiservice
+            .pointer("/spec/service")
+            .filter(|service| !service.is_null())
+        else {
+            continue;
+        };
+        assess(
+            service,
+            serde_json::json!({
+                "severity": "high",
+                "kind": "APIService",
+                "name": metadata_str(apiservice, "name"),
+                "impact": "the aggregated API is unavailable until its Service runs, which stalls API discovery and namespace deletion"
+            }),
+        );
+    }
+
+    let rank = |risk: &Value| {
+        RESTORE_RISK_SEVERITIES
+            .iter()
+            .position(|severity| risk["severity"] == *severity)
+            .unwrap_or(RESTORE_RISK_SEVERITIES.len())
+    };
+    risks.sort_by_key(rank);
+    let by_severity: BTreeMap<&str, usize> = RESTORE_RISK_SEVERITIES
+        .iter()
+        .map(|severity| {
+            let count = risks.iter().filter(|r| r["severity"] == *severity).count();
+            (*severity, count)
+        })
+        .collect();
+
+    serde_json::json!({
+        "total_risks": risks.len(),
+        "by_severity": by_severity,
+        "risks": risks
+    })
+}
+
+/// Secret type holding a certificate chain in tls.crt and its private key in tls.key
+pub const TLS_SECRET: &str = "kubernetes.io/tls";
+
+/// Subject, issuer and expiry of the leaf certificate of a TLS Secret
+#[derive(Serialize)]
+struct CertificateInfo {
+    subject: String,
+    issuer: String,
+    not_after: String,
+    expired: bool,
+    chain_length: usize,
+}
+
+/// Parse the leaf (first) certificate of a base64-encoded PEM tls.crt value
+fn parse_tls_certificate(encoded: &str) -> Result<CertificateInfo, String> {
+    let pem_bytes = BASE64_STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("invalid base64: {}", e))?;
+    let chain: Vec<pem::Pem> = pem::parse_many(&pem_bytes)
+        .map_err(|e| format!("invalid PEM: {}", e))?
+        .into_iter()
+        .filter(|block| block.tag() == "CERTIFICATE")
+        .collect();
+    let leaf = chain.first().ok_or("no CERTIFICATE block in tls.crt")?;
+    let (_, certificate) = x509_parser::parse_x509_certificate(leaf.contents())
+        .map_err(|e| format!("invalid certificate: {}", e))?;
+
+    let not_after = certificate.validity().not_after.timestamp();
+    Ok(CertificateInfo {
+        subject: certificate.subject().to_string(),
+        issuer: certificate.issuer().to_string(),
+        not_after: chrono::DateTime::from_timestamp(not_after, 0)
+            .map(|time| time.to_rfc3339())
+            .unwrap_or_default(),
+        expired: not_after < chrono::Utc::now().timestamp(),
+        chain_length: chain.len(),
+    })
+}
+
+/// Report the leaf certificate of every kubernetes.io/tls Secret, never its private key,
+/// counting expired certificates and those that could not be parsed
+pub fn analyze_tls_certificates(secrets: &[Value]) -> Value {
+    let mut expired = 0;
+    let mut unparseable = 0;
+
+    let certificates: Vec<Value> = secrets
+        .iter()
+        .filter(|secret| secret.get("type").and_then(|t| t.as_str()) == Some(TLS_SECRET))
+        .map(|secret| {
+            let certificate = match secret.pointer("/data/tls.crt").and_then(|c| c.as_str()) {
+                Some(encoded) => parse_tls_certificate(encoded),
+                None => Err("no tls.crt".to_string()),
+            };
+            let (certificate, error) = match certificate {
+                Ok(certificate) => {
+                    if certificate.expired {
+                        expired += 1;
+                    }
+                    (Some(certificate), None)
+                }
+                Err(error) => {
+                    unparseable += 1;
+                    (None, Some(error))
+                }
+            };
+            serde_json::json!({
+                "namespace": metadata_str(secret, "namespace"),
+                "name": metadata_str(secret, "name"),
+                "certificate": certificate,
+                "error": error
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "total_tls_secrets": certificates.len(),
+        "expired": expired,
+        "unparseable": unparseable,
+        "certificates": certificates
+    })
+}
+
+/// Whether a toleration matches a taint, following the scheduler's rules: an empty key with
+/// Exists matches every taint, and an empty effect matches every effect
+fn tolerates(toleration: &Value, taint: &Value) -> bool {
+    let toleration_field = |name: &str| toleration.get(name).and_then(|v| v.as_str());
+    let taint_field = |name: &str| taint.get(name).and_then(|v| v.as_str());
+    let exists = toleration_field("operator") == Some("Exists");
+
+    let key_matches = match toleration_field("key") {
+        None | Some("") => exists,
+        key => key == taint_field("key"),
+    };
+    let value_matches = exists
+        || toleration_field("value").unwrap_or_default()
+            == taint_field("value").unwrap_or_default();
+    let effect_matches = match toleration_field("effect") {
+        None | Some("") => true,
+        effect => effect == taint_field("effect"),
+    };
+    key_matches && value_matches && effect_matches
+}
+
+/// Report tainted nodes and, for each Pod and Deployment with tolerations, the tainted nodes
+/// whose NoSchedule and NoExecute taints it tolerates
+pub fn analyze_scheduling(nodes: &[Value], workloads: &[Value]) -> Value {
+    let taints = |node: &Value| -> Vec<Value> {
+        node.pointer("/spec/taints")
+            .and_then(|t| t.as_array())
+            .cloned()
+            .unwrap_or_default()
+    };
+    let tainted_nodes: Vec<(&str, Vec<Value>)> = nodes
+        .iter()
+        .filter_map(|node| Some((metadata_str(node, "name")?, taints(node))))
+        .filter(|(_, taints)| !taints.is_empty())
+        .collect();
+
+    let mut tolerations_by_key: BTreeMap<String, usize> = BTreeMap::new();
+    let mut tolerating_workloads = Vec::new();
+    for workload in workloads {
+        let Some(tolerations) = pod_spec(workload)
+            .and_then(|spec| spec.get("tolerations"))
+            .and_then(|t| t.as_array())
+            .filter(|t| !t.is_empty())
+        else {
+            continue;
+        };
+
+        for toleration in tolerations {
+            let key = toleration
+                .get("key")
+                .and_then(|k| k.as_str())
+                .filter(|k| !k.is_empty())
+                .unwrap_or("*");
+            *tolerations_by_key.entry(key.to_string()).or_default() += 1;
+        }
+
+        let tolerated_nodes: Vec<&str> = tainted_nodes
+            .iter()
+            .filter(|(_, taints)| {
+                taints
+                    .iter()
+                    .filter(|taint| {
+                        matches!(
+                            taint.get("effect").and_then(|e| e.as_str()),
+                            Some("NoSchedule" | "NoExecute")
+                        )
+                    })
+                    .all(|taint| tolerations.iter().any(|t| tolerates(t, taint)))
+            })
+            .map(|(name, _)| *name)
+            .collect();
+
+        tolerating_workloads.push(serde_json::json!({
+            "namespace": metadata_str(workload, "namespace"),
+            "kind": workload.get("kind"),
+            "name": metadata_str(workload, "name"),
+            "tolerations": tolerations,
+            "tolerated_nodes": tolerated_nodes
+        }));
+    }
+
+    serde_json::json!({
+        "total_nodes": nodes.len(),
+        "tainted_nodes": tainted_nodes
+            .iter()
+            .map(|(name, taints)| serde_json::json!({ "name": name, "taints": taints }))
+            .collect::<Vec<_>>(),
+        "tolerations_by_key": tolerations_by_key,
+        "workloads_with_tolerations": tolerating_workloads
+    })
+}
+
+/// CPU and memory requests and limits summed over pods
+#[derive(Debug, Clone, Default, Serialize)]
+struct ResourceTotals {
+    pods: usize,
+    cpu_requests_cores: f64,
+    memory_requests_bytes: f64,
+    cpu_limits_cores: f64,
+    memory_limits_bytes: f64,
+    qos_classes: BTreeMap<String, usize>,
+}
+
+impl ResourceTotals {
+    fn add_pod(&mut self, pod: &Value) {
+        let containers = pod
+            .pointer("/spec/containers")
+            .and_then(|c| c.as_array())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let quantity = |container: &Value, kind: &str, resource: &str| {
+            container
+                .pointer(&format!("/resources/{}/{}", kind, resource))
+                .and_then(|q| q.as_str())
+                .and_then(parse_quantity)
+        };
+
+        self.pods += 1;
+        for container in containers {
+            self.cpu_requests_cores += quantity(container, "requests", "cpu").unwrap_or_default();
+            self.memory_requests_bytes +=
+                quantity(container, "requests", "memory").unwrap_or_default();
+            self.cpu_limits_cores += quantity(container, "limits", "cpu").unwrap_or_default();
+            self.memory_limits_bytes += quantity(container, "limits", "memory").unwrap_or_default();
+        }
+
+        // The API server sets status.qosClass; derive it the same way for pods without status
+        let qos_class = pod
+            .pointer("/status/qosClass")
+            .and_then(|q| q.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                let guaranteed = !containers.is_empty()
+                    && containers.iter().all(|container| {
+                        ["cpu", "memory"].iter().all(|resource| {
+                            let limit = quantity(container, "limits", resource);
+                            let request = quantity(container, "requests", resource).or(limit);
+                            limit.is_some() && request == limit
+                        })
+                    });
+                let best_effort = containers.iter().all(|container| {
+                    ["requests", "limits"].iter().all(|kind| {
+                        container
+                            .pointer(&format!("/resources/{}", kind))
+                            .and_then(|r| r.as_object())
+                            .is_none_or(|r| r.is_empty())
+                    })
+                });
+                match (guaranteed, best_effort) {
+                    (true, _) => "Guaranteed",
+                    (false, true) => "BestEffort",
+                    (false, false) => "Burstable",
+                }
+                .to_string()
+            });
+        *self.qos_classes.entry(qos_class).or_default() += 1;
+    }
+}
+
+/// Sum container CPU/memory requests and limits per namespace and cluster-wide, and count pods
+/// per QoS class. Only Pods are summed, as Deployment templates describe the same pods again
+pub fn analyze_resource_requests(pods: &[Value]) -> Value {
+    let mut cluster = ResourceTotals::default();
+    let mut namespaces: BTreeMap<&str, ResourceTotals> = BTreeMap::new();
+
+    for pod in pods {
+        cluster.add_pod(pod);
+        namespaces
+            .entry(metadata_str(pod, "namespace").unwrap_or("unknown"))
+            .or_default()
+            .add_pod(pod);
+    }
+
+    serde_json::json!({
+        "cluster": cluster,
+        "namespaces": namespaces
+    })
+}
+
+/// Graph node label: kind/namespace/name
+fn graph_node(kind: &str, namespace: &str, name: &str) -> String {
+    format!("{}/{}/{}", kind, namespace, name)
+}
+
+/// Build a Graphviz DOT graph of ownerReferences, Service selectors, Ingress backends and
+/// workload Secret/ConfigMap references between collected namespaced resources
+pub fn dependency_graph(resources: &[Value]) -> String {
+    let mut nodes: BTreeSet<String> = BTreeSet::new();
+    let mut edges: BTreeSet<(String, String, &str)> = BTreeSet::new();
+
+    for resource in resources {
+        let (Some(kind), Some(namespace), Some(name)) = (
+            resource.get("kind").and_then(|k| k.as_str()),
+            metadata_str(resource, "namespace"),
+            metadata_str(resource, "name"),
+        ) else {
+            continue;
+        };
+        let node = graph_node(kind, namespace, name);
+        nodes.insert(node.clone());
+        let mut edge_to = |target_kind: &str, target_name: &str, label| {
+            let target = graph_node(target_kind, namespace, target_name);
+            nodes.insert(target.clone());
+            edges.insert((node.clone(), target, label));
+        };
+
+        for owner in resource
+            .pointer("/metadata/ownerReferences")
+            .and_then(|o| o.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if let (Some(owner_kind), Some(owner_name)) = (
+                owner.get("kind").and_then(|k| k.as_str()),
+                owner.get("name").and_then(|n| n.as_str()),
+            ) {
+                edge_to(owner_kind, owner_name, "owner");
+            }
+        }
+
+        match kind {
+            "Service" => {
+                let Some(selector) = resource
+                    .pointer("/spec/selector")
+                    .filter(|s| s.as_object().is_some_and(|s| !s.is_empty()))
+                else {
+                    continue;
+                };
+                let selector = serde_json::json!({ "matchLabels": selector });
+                for pod in resources.iter().filter(|r| {
+                    r.get("kind").and_then(|k| k.as_str()) == Some("Pod")
+                        && metadata_str(r, "namespace") == Some(namespace)
+                }) {
+                    let labels = pod
+                        .pointer("/metadata/labels")
+                        .and_then(|l| l.as_object())
+                        .cloned()
+                        .unwrap_or_default();
+                    if selector_matches(&selector, &labels)
+                        && let Some(pod_name) = metadata_str(pod, "name")
+                    {
+                        edge_to("Pod", pod_name, "selects");
+                    }
+                }
+            }
+            "Ingress" => {
+                let default_backend = resource.pointer("/spec/defaultBackend/service/name");
+                let rule_backends = resource
+                    .pointer("/spec/rules")
+                    .and_then(|r| r.as_array())
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|rule| {
+                        rule.pointer("/http/paths")
+                            .and_then(|p| p.as_array())
+                            .into_iter()
+                            .flatten()
+                    })
+                    .filter_map(|path| path.pointer("/backend/service/name"));
+                for service in default_backend.into_iter().chain(rule_backends) {
+                    if let Some(service) = service.as_str() {
+                        edge_to("Service", service, "backend");
+                    }
+                }
+                for (target_kind, target_name, field) in ingress_tls_references(resource) {
+                    edge_to(target_kind, target_name, field);
+                }
+            }
+            _ => {
+                if let Some(pod_spec) = pod_spec(resource) {
+                    for (target_kind, target_name, field) in pod_spec_references(pod_spec) {
+                        edge_to(target_kind, target_name, field);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut dot = String::from("digraph ketchup {\n    rankdir=LR;\n    node [shape=box];\n");
+    for node in &nodes {
+        dot.push_str(&format!("    {:?};\n", node));
+    }
+    for (from, to, label) in &edges {
+        dot.push_str(&format!(
+            "    {:?} -> {:?} [label={:?}];\n",
+            from, to, label
+        ));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rancher_backup_deployment() -> Value {
+        json!({
+            "kind": "Deployment",
+            "metadata": {"name": "rancher-backup", "namespace": "cattle-resources-system"},
+            "spec": {"template": {"spec": {"containers": [
+                {"name": "rancher-backup", "image": "rancher/backup-restore-operator:v4.0.2"}
+            ]}}}
+        })
+    }
+
+    #[test]
+    fn rancher_backup_without_crd() {
+        let backup = detect_rancher_backup(&[], None);
+
+        assert_eq!(backup["category"], "Lifecycle");
+        assert_eq!(backup["backup_crd"], false);
+        assert!(backup["operator"].is_null());
+        assert_eq!(backup["scheduled_backups"], 0);
+        assert_eq!(backup["backups"], json!([]));
+    }
+
+    #[test]
+    fn rancher_backup_counts_scheduled_backups() {
+        // Backups as listed through the backups.resources.cattle.io CRD
+        let backups = [
+            json!({
+                "apiVersion": "resources.cattle.io/v1",
+                "kind": "Backup",
+                "metadata": {"name": "nightly"},
+                "spec": {
+                    "resourceSetName": "rancher-resource-set",
+                    "schedule": "0 2 * * *",
+                    "retentionCount": 7,
+                    "storageLocation": {"s3": {"bucketName": "rancher-backups"}},
+                    "encryptionConfigSecretName": "backup-encryption"
+                },
+                "status": {"lastSnapshotTs": "2026-10-15T02:00:00Z"}
+            }),
+            json!({
+                "apiVersion": "resources.cattle.io/v1",
+                "kind": "Backup",
+                "metadata": {"name": "before-upgrade"},
+                "spec": {"resourceSetName": "rancher-resource-set"}
+            }),
+        ];
+
+        let backup = detect_rancher_backup(&[rancher_backup_deployment()], Some(&backups));
+
+        assert_eq!(backup["backup_crd"], true);
+        assert_eq!(backup["operator"]["version"], "v4.0.2");
+        assert_eq!(backup["scheduled_backups"], 1);
+
+        let nightly = &backup["backups"][0];
+        assert_eq!(nightly["schedule"], "0 2 * * *");
+        assert_eq!(nightly["retention_count"], 7);
+        assert_eq!(nightly["storage_location"], "custom");
+        assert_eq!(nightly["encrypted"], true);
+
+        let one_off = &backup["backups"][1];
+        assert!(one_off["schedule"].is_null());
+        assert_eq!(one_off["storage_location"], "operator default");
+        assert_eq!(one_off["encrypted"], false);
+    }
+
+    fn workload(kind: &str, namespace: &str, name: &str, image: &str) -> Value {
+        json!({
+            "kind": kind,
+            "metadata": {"name": name, "namespace": namespace},
+            "spec": {"template": {"spec": {"containers": [{"name": name, "image": image}]}}}
+        })
+    }
+
+    #[test]
+    fn prometheus_stack_from_rancher_monitoring() {
+        let deployments = [
+            workload(
+                "Deployment",
+                "cattle-monitoring-system",
+                "rancher-monitoring-operator",
+                "rancher/mirrored-prometheus-operator-prometheus-operator:v0.65.1",
+            ),
+            workload(
+                "Deployment",
+                "cattle-monitoring-system",
+                "rancher-monitoring-grafana",
+                "rancher/mirrored-grafana-grafana:9.1.5",
+            ),
+        ];
+        let statefulsets = [workload(
+            "StatefulSet",
+            "cattle-monitoring-system",
+            "prometheus-rancher-monitoring-prometheus",
+            "rancher/mirrored-prometheus-prometheus:v2.42.0",
+        )];
+
+        // The prometheuses.monitoring.coreos.com CRD is installed, alertmanagers is not
+        let stack = detect_prometheus_stack(&deployments, &statefulsets, true, false);
+
+        assert_eq!(stack["category"], "Observability");
+        assert_eq!(stack["rancher_monitoring"], true);
+        assert_eq!(stack["prometheus_crd"], true);
+        assert_eq!(stack["alertmanager_crd"], false);
+        assert_eq!(stack["operator"]["version"], "v0.65.1");
+        assert_eq!(stack["prometheus"]["version"], "v2.42.0");
+        assert_eq!(
+            stack["prometheus"]["workload"],
+            "StatefulSet cattle-monitoring-system/prometheus-rancher-monitoring-prometheus"
+        );
+        assert_eq!(stack["grafana"]["version"], "9.1.5");
+    }
+
+    #[test]
+    fn prometheus_stack_outside_monitoring_namespace_is_ignored() {
+        let statefulsets = [workload(
+            "StatefulSet",
+            "apps",
+            "prometheus-app",
+            "prom/prometheus:v2.45.0",
+        )];
+
+        let stack = detect_prometheus_stack(&[], &statefulsets, false, false);
+
+        assert_eq!(stack["rancher_monitoring"], false);
+        assert!(stack["prometheus"].is_null());
+    }
+
+    #[test]
+    fn calico_detected_in_calico_system() {
+        let daemonsets = [workload(
+            "DaemonSet",
+            "calico-system",
+            "calico-node",
+            "docker.io/rancher/mirrored-calico-node:v3.27.3",
+        )];
+
+        let cni = detect_cni(&daemonsets, &["ippools.crd.projectcalico.org"]).unwrap();
+
+        assert_eq!(cni.name, "Calico");
+        assert_eq!(cni.version, "v3.27.3");
+        assert_eq!(cni.daemonset.as_deref(), Some("calico-system/calico-node"));
+        assert_eq!(cni.crd.as_deref(), Some("ippools.crd.projectcalico.org"));
+    }
+
+    #[test]
+    fn cni_detected_from_crd_alone() {
+        let cni = detect_cni(&[], &["ciliumnetworkpolicies.cilium.io"]).unwrap();
+
+        assert_eq!(cni.name, "Cilium");
+        assert_eq!(cni.version, "unknown");
+        assert!(cni.daemonset.is_none());
+        assert!(detect_cni(&[], &[]).is_none());
+    }
+
+    #[test]
+    fn harvester_with_kubevirt_and_longhorn_is_hyperconverged() {
+        let deployments = [
+            workload(
+                "Deployment",
+                "harvester-system",
+                "harvester",
+                "rancher/harvester:v1.3.1",
+            ),
+            workload(
+                "Deployment",
+                "harvester-system",
+                "virt-operator",
+                "registry.suse.com/suse/sles/15.5/virt-operator:1.1.0-150500.8.6.1",
+            ),
+        ];
+
+        let virtualization = detect_harvester(
+            &deployments,
+            &[],
+            &["virtualmachines.harvesterhci.io"],
+            true,
+        );
+
+        assert_eq!(virtualization["category"], "Virtualization");
+        assert_eq!(virtualization["harvester"]["version"], "v1.3.1");
+        assert_eq!(
+            virtualization["harvester_crds"],
+            json!(["virtualmachines.harvesterhci.io"])
+        );
+        assert_eq!(virtualization["kubevirt"], true);
+        assert_eq!(virtualization["workloads"].as_array().unwrap().len(), 2);
+        assert_eq!(virtualization["hyperconverged"], true);
+        assert!(virtualization["recommendation"].is_string());
+    }
+
+    #[test]
+    fn harvester_crd_without_longhorn_is_not_hyperconverged() {
+        let virtualization = detect_harvester(
+            &[],
+            &[],
+            &[
+                "virtualmachines.harvesterhci.io",
+                "virtualmachines.kubevirt.io",
+            ],
+            false,
+        );
+
+        assert!(virtualization["harvester"].is_null());
+        assert_eq!(virtualization["kubevirt"], true);
+        assert_eq!(virtualization["hyperconverged"], false);
+        assert!(virtualization["recommendation"].is_null());
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use similar::TextDiff;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use tracing::{debug, info};
+
+/// Metadata fields that change on every write and would make every object look modified
+const VOLATILE_METADATA_FIELDS: &[&str] = &[
+    "resourceVersion",
+    "uid",
+    "creationTimestamp",
+    "generation",
+    "managedFields",
+    "selfLink",
+];
+
+/// Resources are matched between collections by (namespace, kind, name)
+pub type ResourceKey = (String, String, String);
+
+/// Compare two extracted collections and write diff-report.yaml to output_dir
+pub fn diff_collections(old_dir: &str, new_dir: &str, output_dir: &str) -> Result<()> {
+    info!("Comparing collections {} and {}", old_dir, new_dir);
+
+    let old = load_collection(old_dir)?;
+    let new = load_collection(new_dir)?;
+    info!(
+        "Loaded {} resources from {} and {} from {}",
+        old.len(),
+        old_dir,
+        new.len(),
+        new_dir
+    );
+
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut changed = Vec::new();
+    let mut unchanged = 0;
+    let mut per_namespace_kind: BTreeMap<(String, String), [usize; 3]> = BTreeMap::new();
+
+    let keys: BTreeSet<&ResourceKey> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        let (namespace, kind, name) = key;
+        let entry = serde_json::json!({ "namespace": namespace, "kind": kind, "name": name });
+        let counts = per_namespace_kind
+            .entry((namespace.clone(), kind.clone()))
+            .or_default();
+
+        match (old.get(key), new.get(key)) {
+            (None, Some(_)) => {
+                counts[0] += 1;
+                added.push(entry);
+            }
+            (Some(_), None) => {
+                counts[1] += 1;
+                removed.push(entry);
+            }
+            (Some(old_resource), Some(new_resource)) if old_resource != new_resource => {
+                counts[2] += 1;
+                let old_yaml = serde_yaml::to_string(old_resource)?;
+                let new_yaml = serde_yaml::to_string(new_resource)?;
+                let unified = TextDiff::from_lines(&old_yaml, &new_yaml)
+                    .unified_diff()
+                    .header(
+                        &format!("old/{}/{}/{}", namespace, kind, name),
+                        &format!("new/{}/{}/{}", namespace, kind, name),
+                    )
+                    .to_string();
+
+                let mut entry = entry;
+                entry["diff"] = Value::String(unified);
+                changed.push(entry);
+            }
+            _ => unchanged += 1,
+        }
+    }
+
+    let mut ignored_fields: Vec<String> = VOLATILE_METADATA_FIELDS
+        .iter()
+        .map(|field| format!("metadata.{}", field))
+        .collect();
+    ignored_fields.push("status".to_string());
+
+    let report = serde_json::json!({
+        "old_collection": old_dir,
+        "new_collection": new_dir,
+        "ignored_fields": ignored_fields,
+        "summary": {
+            "added": added.len(),
+            "removed": removed.len(),
+            "changed": changed.len(),
+            "unchanged": unchanged
+        },
+        "added": added,
+        "removed": removed,
+        "changed": changed
+    });
+
+    fs::create_dir_all(output_dir).context("Failed to create diff output directory")?;
+    let filename = format!("{}/diff-report.yaml", output_dir);
+    let content = serde_yaml::to_string(&report).context("Failed to serialize diff report")?;
+    fs::write(&filename, content).context("Failed to write diff report")?;
+    info!("Diff report written to {}", filename);
+
+    println!(
+        "{} added, {} removed, {} changed, {} unchanged",
+        report["summary"]["added"],
+        report["summary"]["removed"],
+        report["summary"]["changed"],
+        unchanged
+    );
+    for ((namespace, kind), [added, removed, changed]) in &per_namespace_kind {
+        if added + removed + changed > 0 {
+            println!(
+                "  {:<30} {:<24} +{} -{} ~{}",
+                namespace, kind, added, removed, changed
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Load every resource manifest under a collection directory, keyed by (namespace, kind, name)
+pub fn load_collection(dir: &str) -> Result<BTreeMap<ResourceKey, Value>> {
+    let root = Path::new(dir);
+    if !root.is_dir() {
+        anyhow::bail!("{} is not a directory", dir);
+    }
+
+    let mut resources = BTreeMap::new();
+    load_directory(root, &mut resources)?;
+    Ok(resources)
+}
+
+fn load_directory(dir: &Path, resources: &mut BTreeMap<ResourceKey, Value>) -> Result<()> {
+    let entries =
+        fs::read_dir(dir).with_context(|| format!("Failed to read directory {}", dir.display()))?;
+
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            load_directory(&path, resources)?;
+            continue;
+        }
+
+        let content = match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("json") => fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?,
+            _ => continue,
+        };
+
+        // YAML is a superset of JSON, so one parser handles both formats
+        let Ok(mut resource) = serde_yaml::from_str::<Value>(&content) else {
+            debug!("Skipping unparseable file {}", path.display());
+            continue;
+        };
+
+        // Summaries and analysis reports are not resources
+        let Some(key) = resource_key(&resource) else {
+            continue;
+        };
+
+        normalize(&mut resource);
+        resources.insert(key, resource);
+    }
+
+    Ok(())
+}
+
+/// Key of a resource, or None for documents without a kind and name
+pub fn resource_key(resource: &Value) -> Option<ResourceKey> {
+    let kind = resource.get("kind").and_then(|k| k.as_str())?;
+    let metadata = resource.get("metadata")?;
+    let name = metadata.get("name").and_then(|n| n.as_str())?;
+    let namespace = metadata
+        .get("namespace")
+        .and_then(|n| n.as_str())
+        .unwrap_or("");
+
+    Some((namespace.to_string(), kind.to_string(), name.to_string()))
+}
+
+/// Whether a resource is identical to its baseline counterpart, ignoring volatile fields
+pub fn is_unchanged(resource: &Value, baseline: &BTreeMap<ResourceKey, Value>) -> bool {
+    let Some(previous) = resource_key(resource).and_then(|key| baseline.get(&key)) else {
+        return false;
+    };
+
+    let mut current = resource.clone();
+    normalize(&mut current);
+    &current == previous
+}
+
+/// Drop fields that change without any configuration change
+fn normalize(resource: &mut Value) {
+    if let Some(metadata) = resource.get_mut("metadata").and_then(|m| m.as_object_mut()) {
+        for field in VOLATILE_METADATA_FIELDS {
+            metadata.remove(*field);
+        }
+    }
+
+    if let Some(object) = resource.as_object_mut() {
+        object.remove("status");
+    }
+}
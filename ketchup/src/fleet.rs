@@ -0,0 +1,46 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use tracing::info;
+
+/// Fleet file listing the clusters to collect
+#[derive(Debug, Deserialize)]
+pub struct FleetFile {
+    pub clusters: Vec<FleetCluster>,
+}
+
+/// A single cluster entry in the fleet file
+#[derive(Debug, Clone, Deserialize)]
+pub struct FleetCluster {
+    pub name: String,
+    pub kubeconfig: String,
+    #[serde(default)]
+    pub context: Option<String>,
+}
+
+/// Load and validate a fleet file
+pub fn load_fleet_file(path: &str) -> Result<Vec<FleetCluster>> {
+    info!("Loading fleet file: {}", path);
+
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read fleet file {}", path))?;
+    let fleet: FleetFile = serde_yaml::from_str(&content)
+        .with_context(|| format!("Failed to parse fleet file {}", path))?;
+
+    if fleet.clusters.is_empty() {
+        anyhow::bail!("Fleet file {} does not list any clusters", path);
+    }
+
+    let mut names = HashSet::new();
+    for cluster in &fleet.clusters {
+        if cluster.name.is_empty() || cluster.name.contains('/') {
+            anyhow::bail!("Invalid cluster name '{}' in fleet file", cluster.name);
+        }
+        if !names.insert(cluster.name.as_str()) {
+            anyhow::bail!("Duplicate cluster name '{}' in fleet file", cluster.name);
+        }
+    }
+
+    Ok(fleet.clusters)
+}
@@ -0,0 +1,31 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::warn;
+
+/// Exit code used when a collection was interrupted with Ctrl-C
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Listen for Ctrl-C. The first interrupt asks the collection to wind down and
+/// write partial output, a second one exits immediately.
+pub fn install_handler() {
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        warn!(
+            "Interrupt received, finishing current step and writing partial output (Ctrl-C again to abort)"
+        );
+        INTERRUPTED.store(true, Ordering::SeqCst);
+
+        if tokio::signal::ctrl_c().await.is_ok() {
+            warn!("Second interrupt received, aborting");
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    });
+}
+
+/// Whether the user has asked to stop the collection
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
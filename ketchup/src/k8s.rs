@@ -1,30 +1,643 @@
+use crate::interrupt;
+use crate::throttle::RateLimiter;
 use anyhow::{Context, Result};
-use k8s_openapi::api::apps::v1::Deployment;
-use k8s_openapi::api::core::v1::{ConfigMap, Namespace, Pod, Secret, Service};
-use kube::{Api, Client, Config};
+use futures::{StreamExt, stream};
+use k8s_openapi::api::admissionregistration::v1::{
+    MutatingWebhookConfiguration, ValidatingWebhookConfiguration,
+};
+use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, StatefulSet};
+use k8s_openapi::api::authorization::v1::{
+    ResourceAttributes, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec,
+};
+use k8s_openapi::api::core::v1::{
+    ConfigMap, Namespace, Node, PersistentVolume, PersistentVolumeClaim, Pod, ResourceQuota,
+    Secret, Service, ServiceAccount,
+};
+use k8s_openapi::api::networking::v1::Ingress;
+use k8s_openapi::api::policy::v1::PodDisruptionBudget;
+use k8s_openapi::api::rbac::v1::{ClusterRole, ClusterRoleBinding, Role, RoleBinding};
+use k8s_openapi::api::storage::v1::StorageClass;
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use k8s_openapi::kube_aggregator::pkg::apis::apiregistration::v1::APIService;
+use k8s_openapi::{ClusterResourceScope, NamespaceResourceScope};
+use kube::api::{ApiResource, DynamicObject, ListParams, PostParams};
+use kube::config::{
+    AuthInfo, Cluster, KubeConfigOptions, Kubeconfig, NamedAuthInfo, NamedCluster, NamedContext,
+};
+use kube::{Api, Client, Config, Resource};
+use secrecy::SecretString;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tracing::{debug, info, warn};
 
+/// List requests per second allowed when --qps is not given
+pub const DEFAULT_QPS: u32 = 20;
+
+/// How often workloads are re-checked while waiting for quiescence
+const QUIESCENCE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Make sure the exec credential plugin of the selected context can be found. kube only
+/// reports a generic auth failure when it cannot spawn the plugin.
+fn check_exec_plugin(kubeconfig: &Kubeconfig, context: Option<&str>) -> Result<()> {
+    let Some(context_name) = context.or(kubeconfig.current_context.as_deref()) else {
+        return Ok(());
+    };
+
+    let Some(user) = kubeconfig
+        .contexts
+        .iter()
+        .find(|c| c.name == context_name)
+        .and_then(|c| c.context.as_ref())
+        .map(|c| c.user.as_str())
+    else {
+        return Ok(());
+    };
+
+    let Some(command) = kubeconfig
+        .auth_infos
+        .iter()
+        .find(|a| a.name == user)
+        .and_then(|a| a.auth_info.as_ref())
+        .and_then(|a| a.exec.as_ref())
+        .and_then(|e| e.command.as_deref())
+    else {
+        return Ok(());
+    };
+
+    if !command_exists(command) {
+        anyhow::bail!(
+            "Exec credential plugin '{}' used by kubeconfig user '{}' was not found on PATH; install it or fix users[].user.exec.command",
+            command,
+            user
+        );
+    }
+
+    debug!(
+        "Using exec credential plugin '{}' for user '{}'",
+        command, user
+    );
+    Ok(())
+}
+
+/// Check whether a command is an existing file path or can be found on PATH
+fn command_exists(command: &str) -> bool {
+    let path = Path::new(command);
+    if path.components().count() > 1 {
+        return path.is_file();
+    }
+
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(command).is_file()))
+        .unwrap_or(false)
+}
+
+/// Closest existing namespace to a mistyped one, compared case-insensitively,
+/// if it is within a few edits
+fn closest_namespace<'a>(requested: &str, available: &'a [String]) -> Option<&'a str> {
+    let requested = requested.to_lowercase();
+    let max_distance = (requested.chars().count() / 3).max(2);
+
+    available
+        .iter()
+        .map(|ns| (ns.as_str(), levenshtein(&requested, &ns.to_lowercase())))
+        .filter(|(_, distance)| *distance <= max_distance)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(ns, _)| ns)
+}
+
+/// Edit distance between two strings
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// API server credentials given on the command line instead of a kubeconfig
+pub struct TokenAuth {
+    pub server: String,
+    pub token: Option<String>,
+    pub token_file: Option<String>,
+    pub ca_file: Option<String>,
+    pub insecure_skip_tls_verify: bool,
+}
+
+impl TokenAuth {
+    /// Single-context kubeconfig for the server and token, so kube handles CA and token
+    /// files the same way as for a kubeconfig on disk
+    fn to_kubeconfig(&self) -> Kubeconfig {
+        const NAME: &str = "ketchup";
+
+        Kubeconfig {
+            clusters: vec![NamedCluster {
+                name: NAME.to_string(),
+                cluster: Some(Cluster {
+                    server: Some(self.server.clone()),
+                    certificate_authority: self.ca_file.clone(),
+                    insecure_skip_tls_verify: Some(self.insecure_skip_tls_verify),
+                    ..Default::default()
+                }),
+            }],
+            auth_infos: vec![NamedAuthInfo {
+                name: NAME.to_string(),
+                auth_info: Some(AuthInfo {
+                    token: self.token.clone().map(SecretString::new),
+                    token_file: self.token_file.clone(),
+                    ..Default::default()
+                }),
+            }],
+            contexts: vec![NamedContext {
+                name: NAME.to_string(),
+                context: Some(kube::config::Context {
+                    cluster: NAME.to_string(),
+                    user: NAME.to_string(),
+                    ..Default::default()
+                }),
+            }],
+            current_context: Some(NAME.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Namespaces where cluster add-ons such as the CNI and ingress controller are installed
+const SYSTEM_NAMESPACES: &[&str] = &[
+    "kube-system",
+    "calico-system",
+    "ingress-nginx",
+    "gpu-operator",
+    "metallb-system",
+    "cattle-system",
+    "cattle-resources-system",
+    "cattle-monitoring-system",
+    "harvester-system",
+    "observability",
+    "opentelemetry-operator-system",
+];
+
+fn system_namespaces() -> Vec<String> {
+    SYSTEM_NAMESPACES.iter().map(|ns| ns.to_string()).collect()
+}
+
+/// Order resources by (namespace, name) so output does not depend on how concurrent
+/// list requests completed
+fn sort_by_namespace_and_name(resources: &mut [Value]) {
+    let key = |resource: &Value, field: &str| {
+        resource
+            .get("metadata")
+            .and_then(|m| m.get(field))
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    resources.sort_by_cached_key(|resource| (key(resource, "namespace"), key(resource, "name")));
+}
+
+/// DER-encoded certificates from a PEM CA bundle
+fn read_ca_certificates(path: &str) -> Result<Vec<Vec<u8>>> {
+    let content =
+        std::fs::read(path).with_context(|| format!("Failed to read CA file {}", path))?;
+    let certificates: Vec<Vec<u8>> = pem::parse_many(&content)
+        .with_context(|| format!("Failed to parse CA file {}", path))?
+        .into_iter()
+        .filter(|block| block.tag() == "CERTIFICATE")
+        .map(|block| block.into_contents())
+        .collect();
+
+    if certificates.is_empty() {
+        anyhow::bail!("CA file {} does not contain any PEM certificates", path);
+    }
+    Ok(certificates)
+}
+
+/// DER-encoded certificates of the system trust store, which the client trusts when the
+/// kubeconfig cluster has no CA of its own. Certificates rustls cannot use as roots are skipped,
+/// as the client does for its default trust store
+fn native_root_certificates() -> Result<Vec<Vec<u8>>> {
+    let certificates = rustls_native_certs::load_native_certs()
+        .context("Failed to load the system CA certificates")?;
+    Ok(certificates
+        .into_iter()
+        .map(|certificate| certificate.0)
+        .filter(|der| {
+            rustls::RootCertStore::empty()
+                .add(&rustls::Certificate(der.clone()))
+                .is_ok()
+        })
+        .collect())
+}
+
+/// metrics.k8s.io types are not part of k8s-openapi, so they are listed dynamically
+fn metrics_resource(kind: &str, plural: &str) -> ApiResource {
+    ApiResource {
+        group: "metrics.k8s.io".to_string(),
+        version: "v1beta1".to_string(),
+        api_version: "metrics.k8s.io/v1beta1".to_string(),
+        kind: kind.to_string(),
+        plural: plural.to_string(),
+    }
+}
+
+/// Custom resources (Gatekeeper policies, K3s/RKE2 HelmCharts) have no k8s-openapi types, so
+/// they are listed dynamically
+fn custom_resource(group: &str, version: &str, kind: &str, plural: &str) -> ApiResource {
+    ApiResource {
+        group: group.to_string(),
+        version: version.to_string(),
+        api_version: format!("{}/{}", group, version),
+        kind: kind.to_string(),
+        plural: plural.to_string(),
+    }
+}
+
+/// Resource types listed in every collected namespace, as (API group, resource)
+const NAMESPACED_LIST_TYPES: &[(&str, &str)] = &[
+    ("", "pods"),
+    ("", "services"),
+    ("apps", "deployments"),
+    ("", "configmaps"),
+    ("", "secrets"),
+    ("", "resourcequotas"),
+    ("", "serviceaccounts"),
+    ("", "persistentvolumeclaims"),
+    ("networking.k8s.io", "ingresses"),
+    ("policy", "poddisruptionbudgets"),
+    ("rbac.authorization.k8s.io", "roles"),
+    ("rbac.authorization.k8s.io", "rolebindings"),
+    ("helm.cattle.io", "helmcharts"),
+    ("helm.cattle.io", "helmchartconfigs"),
+];
+
+/// Cluster-scoped resource types listed once per collection, as (API group, resource)
+const CLUSTER_LIST_TYPES: &[(&str, &str)] = &[
+    ("", "namespaces"),
+    ("", "nodes"),
+    ("", "persistentvolumes"),
+    ("storage.k8s.io", "storageclasses"),
+    ("rbac.authorization.k8s.io", "clusterroles"),
+    ("rbac.authorization.k8s.io", "clusterrolebindings"),
+    ("apiextensions.k8s.io", "customresourcedefinitions"),
+    (
+        "admissionregistration.k8s.io",
+        "mutatingwebhookconfigurations",
+    ),
+    (
+        "admissionregistration.k8s.io",
+        "validatingwebhookconfigurations",
+    ),
+    ("apiregistration.k8s.io", "apiservices"),
+];
+
+/// Resource types listed in every system namespace (SYSTEM_NAMESPACES) to detect add-ons, as
+/// (API group, resource)
+const SYSTEM_LIST_TYPES: &[(&str, &str)] = &[
+    ("apps", "daemonsets"),
+    ("apps", "deployments"),
+    ("apps", "statefulsets"),
+];
+
+/// Resource types polled in every collected namespace by --wait-for-quiescence, as
+/// (API group, resource)
+const QUIESCENCE_LIST_TYPES: &[(&str, &str)] = &[
+    ("apps", "deployments"),
+    ("apps", "statefulsets"),
+    ("apps", "daemonsets"),
+];
+
+/// Cluster-scoped custom resource types listed when their CRD is installed, as
+/// (CRD, API group, resource). Gatekeeper has one constraint resource per ConstraintTemplate, so
+/// its whole constraints group is checked
+const CRD_LIST_TYPES: &[(&str, &str, &str)] = &[
+    (
+        "constrainttemplates.templates.gatekeeper.sh",
+        "templates.gatekeeper.sh",
+        "constrainttemplates",
+    ),
+    (
+        "constrainttemplates.templates.gatekeeper.sh",
+        "constraints.gatekeeper.sh",
+        "*",
+    ),
+    (
+        "backups.resources.cattle.io",
+        "resources.cattle.io",
+        "backups",
+    ),
+];
+
+/// metrics.k8s.io resources listed with --include-metrics: nodes once, pods per collected
+/// namespace
+const METRICS_NODES: (&str, &str) = ("metrics.k8s.io", "nodes");
+const METRICS_PODS: (&str, &str) = ("metrics.k8s.io", "pods");
+
+/// Whether the current credentials may list one resource type, from a SelfSubjectAccessReview
+#[derive(Debug, Clone, Serialize)]
+pub struct ListAccess {
+    /// None for cluster-scoped resource types
+    pub namespace: Option<String>,
+    /// Resource qualified by its API group, e.g. deployments.apps
+    pub resource: String,
+    pub allowed: bool,
+    pub reason: Option<String>,
+}
+
+/// Time spent collecting one resource type, summed over every collection of it
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CollectionTiming {
+    /// Elapsed time of the collection calls, including rate limiting
+    pub wall_seconds: f64,
+    /// Time spent in list requests, summed over the namespace fan-out
+    pub request_seconds: f64,
+    pub lists: usize,
+}
+
 pub struct KubeClient {
     client: Client,
+    namespace_concurrency: usize,
+    rate_limiter: RateLimiter,
+    /// (namespace, resource type) lists that failed and so returned no resources
+    failed_lists: Mutex<HashSet<(String, String)>>,
+    field_selector: Option<String>,
+    /// API version to list custom resources at, per API group (--prefer-api-version)
+    preferred_versions: HashMap<String, String>,
+    timings: Mutex<BTreeMap<String, CollectionTiming>>,
 }
 
 impl KubeClient {
-    /// Create a new Kubernetes client using the specified kubeconfig file
-    pub async fn new_client(kubeconfig_path: &str) -> Result<Self> {
+    /// Create a new Kubernetes client using the specified kubeconfig file and optional context
+    pub async fn new_client(
+        kubeconfig_path: &str,
+        context: Option<&str>,
+        extra_ca_file: Option<&str>,
+    ) -> Result<Self> {
         info!("Loading kubeconfig from: {}", kubeconfig_path);
 
-        // Set the KUBECONFIG environment variable (safe in our single-threaded context)
-        unsafe {
-            std::env::set_var("KUBECONFIG", kubeconfig_path);
+        let kubeconfig = Kubeconfig::read_from(kubeconfig_path)
+            .with_context(|| format!("Failed to read kubeconfig {}", kubeconfig_path))?;
+
+        check_exec_plugin(&kubeconfig, context)?;
+
+        if let Some(context) = context {
+            info!("Using kubeconfig context: {}", context);
         }
 
-        let config = Config::infer().await.context("Failed to load kubeconfig")?;
+        Self::from_kubeconfig(kubeconfig, context, extra_ca_file).await
+    }
+
+    /// Create a new Kubernetes client from an API server URL and bearer token, without a
+    /// kubeconfig file
+    pub async fn new_token_client(auth: &TokenAuth) -> Result<Self> {
+        info!(
+            "Connecting to API server {} with a bearer token",
+            auth.server
+        );
+        if auth.insecure_skip_tls_verify {
+            warn!("TLS verification of {} is disabled", auth.server);
+        }
+
+        Self::from_kubeconfig(auth.to_kubeconfig(), None, None).await
+    }
+
+    async fn from_kubeconfig(
+        kubeconfig: Kubeconfig,
+        context: Option<&str>,
+        extra_ca_file: Option<&str>,
+    ) -> Result<Self> {
+        let options = KubeConfigOptions {
+            context: context.map(|c| c.to_string()),
+            ..Default::default()
+        };
+
+        let mut config = Config::from_custom_kubeconfig(kubeconfig, &options)
+            .await
+            .context("Failed to load kubeconfig")?;
+
+        if let Some(extra_ca_file) = extra_ca_file {
+            let certificates = read_ca_certificates(extra_ca_file)?;
+            info!(
+                "Trusting {} additional CA certificates from {}",
+                certificates.len(),
+                extra_ca_file
+            );
+            // Without a kubeconfig CA the client trusts the system roots, which a root_cert
+            // list would replace, so they are kept next to the extra ones
+            let mut roots = match config.root_cert.take() {
+                Some(roots) => roots,
+                None => native_root_certificates()?,
+            };
+            roots.extend(certificates);
+            config.root_cert = Some(roots);
+        }
 
         let client = Client::try_from(config).context("Failed to create Kubernetes client")?;
 
         info!("Successfully connected to Kubernetes cluster");
-        Ok(KubeClient { client })
+        Ok(KubeClient {
+            client,
+            namespace_concurrency: 1,
+            rate_limiter: RateLimiter::new(DEFAULT_QPS),
+            failed_lists: Mutex::new(HashSet::new()),
+            field_selector: None,
+            preferred_versions: HashMap::new(),
+            timings: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    /// Set how many namespaces are listed in parallel for each resource type
+    pub fn with_namespace_concurrency(mut self, namespace_concurrency: usize) -> Self {
+        self.namespace_concurrency = namespace_concurrency.max(1);
+        self
+    }
+
+    /// Limit list requests to this many per second across all concurrent collectors
+    pub fn with_qps(mut self, qps: u32) -> Self {
+        self.rate_limiter = RateLimiter::new(qps);
+        self
+    }
+
+    /// Only list saved resources matching this field selector (e.g. status.phase=Running)
+    pub fn with_field_selector(mut self, field_selector: Option<String>) -> Self {
+        self.field_selector = field_selector;
+        self
+    }
+
+    /// List custom resources of these API groups at the given version when their CRD serves it
+    pub fn with_preferred_versions(mut self, preferred_versions: &[(String, String)]) -> Self {
+        self.preferred_versions = preferred_versions.iter().cloned().collect();
+        self
+    }
+
+    /// Collection time per resource type so far
+    pub fn timings(&self) -> BTreeMap<String, CollectionTiming> {
+        self.timings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn record_timing(&self, resource_name: &str, wall: Duration, request: Duration, lists: usize) {
+        let mut timings = self.timings.lock().unwrap_or_else(|e| e.into_inner());
+        let timing = timings.entry(resource_name.to_string()).or_default();
+        timing.wall_seconds += wall.as_secs_f64();
+        timing.request_seconds += request.as_secs_f64();
+        timing.lists += lists;
+    }
+
+    /// Whether listing a resource type in a namespace failed, leaving its results incomplete
+    pub fn list_failed(&self, namespace: &str, resource_name: &str) -> bool {
+        self.failed_lists
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(&(namespace.to_string(), resource_name.to_string()))
+    }
+
+    /// Remember that listing a resource type in a namespace failed
+    fn record_failed_list(&self, namespace: &str, resource_name: &str) {
+        self.failed_lists
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert((namespace.to_string(), resource_name.to_string()));
+    }
+
+    /// Take the failed (namespace, resource type) lists recorded so far, clearing them so a retry
+    /// pass records its own failures
+    pub fn take_failed_lists(&self) -> HashSet<(String, String)> {
+        std::mem::take(&mut *self.failed_lists.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Replace the recorded failed lists, e.g. with those a retry pass could not recover
+    pub fn set_failed_lists(&self, failed_lists: HashSet<(String, String)>) {
+        *self.failed_lists.lock().unwrap_or_else(|e| e.into_inner()) = failed_lists;
+    }
+
+    /// Get the Kubernetes version reported by the API server
+    pub async fn get_server_version(&self) -> Result<String> {
+        debug!("Fetching API server version...");
+
+        let version = self
+            .client
+            .apiserver_version()
+            .await
+            .context("Failed to get API server version")?;
+
+        Ok(version.git_version)
+    }
+
+    /// UID of the kube-system Namespace, the conventional stable identifier of a cluster
+    pub async fn get_cluster_id(&self) -> Result<String> {
+        debug!("Fetching kube-system namespace UID...");
+
+        let api: Api<Namespace> = Api::all(self.client.clone());
+        let namespace = api
+            .get("kube-system")
+            .await
+            .context("Failed to get the kube-system namespace")?;
+
+        namespace
+            .metadata
+            .uid
+            .context("kube-system namespace has no UID")
+    }
+
+    /// Review whether the current credentials may list each resource type ketchup collects, in
+    /// every namespace it lists them in (the collected ones, and the system namespaces for
+    /// add-on detection) and once for cluster-scoped types. Custom resource types are only
+    /// checked when their CRD is installed, as they are not listed otherwise
+    pub async fn check_list_access(
+        &self,
+        namespaces: &[String],
+        wait_for_quiescence: bool,
+        include_metrics: bool,
+    ) -> Result<Vec<ListAccess>> {
+        let api: Api<SelfSubjectAccessReview> = Api::all(self.client.clone());
+
+        let mut namespaced_types: Vec<(&str, &str)> = NAMESPACED_LIST_TYPES.to_vec();
+        if wait_for_quiescence {
+            namespaced_types.extend(QUIESCENCE_LIST_TYPES);
+        }
+        if include_metrics {
+            namespaced_types.push(METRICS_PODS);
+        }
+        let mut checks: Vec<(Option<&str>, (&str, &str))> = Vec::new();
+        for namespace in namespaces {
+            for list_type in &namespaced_types {
+                if !checks.contains(&(Some(namespace.as_str()), *list_type)) {
+                    checks.push((Some(namespace.as_str()), *list_type));
+                }
+            }
+        }
+        for namespace in SYSTEM_NAMESPACES {
+            for list_type in SYSTEM_LIST_TYPES {
+                if !checks.contains(&(Some(namespace), *list_type)) {
+                    checks.push((Some(namespace), *list_type));
+                }
+            }
+        }
+        checks.extend(
+            CLUSTER_LIST_TYPES
+                .iter()
+                .map(|list_type| (None, *list_type)),
+        );
+        for (crd, group, resource) in CRD_LIST_TYPES {
+            if self.crd_exists(crd).await? {
+                checks.push((None, (group, resource)));
+            }
+        }
+        if include_metrics {
+            checks.push((None, METRICS_NODES));
+        }
+
+        let mut access = Vec::new();
+        for (namespace, (group, resource)) in checks {
+            let review = SelfSubjectAccessReview {
+                spec: SelfSubjectAccessReviewSpec {
+                    resource_attributes: Some(ResourceAttributes {
+                        group: Some(group.to_string()),
+                        resource: Some(resource.to_string()),
+                        verb: Some("list".to_string()),
+                        namespace: namespace.map(str::to_string),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+
+            self.rate_limiter.acquire("selfsubjectaccessreviews").await;
+            let status = api
+                .create(&PostParams::default(), &review)
+                .await
+                .with_context(|| format!("Failed to review list access to {}", resource))?
+                .status;
+            access.push(ListAccess {
+                namespace: namespace.map(str::to_string),
+                resource: if group.is_empty() {
+                    resource.to_string()
+                } else {
+                    format!("{}.{}", resource, group)
+                },
+                allowed: status.as_ref().is_some_and(|status| status.allowed),
+                reason: status
+                    .and_then(|status| status.reason)
+                    .filter(|reason| !reason.is_empty()),
+            });
+        }
+        Ok(access)
     }
 
     /// List all available namespaces in the cluster
@@ -32,6 +645,7 @@ impl KubeClient {
         debug!("Fetching list of namespaces...");
 
         let namespaces: Api<Namespace> = Api::all(self.client.clone());
+        self.rate_limiter.acquire("namespaces").await;
         let namespace_list = namespaces
             .list(&Default::default())
             .await
@@ -55,171 +669,726 @@ impl KubeClient {
         for ns in requested {
             if available.contains(ns) {
                 verified.push(ns.clone());
+            } else if let Some(suggestion) = closest_namespace(ns, &available) {
+                warn!(
+                    "Namespace '{}' does not exist, skipping (did you mean '{}'?)",
+                    ns, suggestion
+                );
             } else {
                 warn!("Namespace '{}' does not exist, skipping", ns);
             }
         }
 
         if verified.is_empty() {
-            anyhow::bail!("No valid namespaces found");
+            anyhow::bail!(
+                "No valid namespaces found. Available namespaces: {}",
+                available.join(", ")
+            );
         }
 
         Ok(verified)
     }
 
-    /// Collect pods from specified namespaces
-    pub async fn collect_pods(&self, namespaces: &[String]) -> Result<Vec<Value>> {
-        let mut all_pods = Vec::new();
+    /// Wait until no Deployment, StatefulSet or DaemonSet in the namespaces is mid-rollout.
+    /// Returns false if the timeout elapsed while workloads were still rolling.
+    pub async fn wait_for_quiescence(&self, namespaces: &[String], timeout: Duration) -> bool {
+        // A timeout beyond what Instant can represent means waiting as long as it takes
+        let deadline = Instant::now()
+            .checked_add(timeout)
+            .unwrap_or_else(|| Instant::now() + Duration::from_secs(u32::MAX.into()));
 
-        for namespace in namespaces {
-            info!("Collecting pods from namespace: {}", namespace);
-            let pods: Api<Pod> = Api::namespaced(self.client.clone(), namespace);
-
-            match pods.list(&Default::default()).await {
-                Ok(pod_list) => {
-                    let pod_count = pod_list.items.len();
-                    for pod in pod_list.items {
-                        if let Ok(json) = serde_json::to_value(&pod) {
-                            all_pods.push(json);
-                        }
-                    }
-                    info!("Found {} pods in namespace {}", pod_count, namespace);
-                }
-                Err(e) => {
-                    warn!("Failed to collect pods from namespace {}: {}", namespace, e);
-                }
+        loop {
+            if interrupt::is_interrupted() {
+                return false;
+            }
+
+            let rolling = self.list_rolling_workloads(namespaces).await;
+            if rolling.is_empty() {
+                info!("All workloads are stable");
+                return true;
             }
-        }
 
-        Ok(all_pods)
+            for workload in &rolling {
+                info!("Still rolling out: {}", workload);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                warn!(
+                    "Timed out waiting for {} workloads to finish rolling out, collecting anyway",
+                    rolling.len()
+                );
+                return false;
+            }
+
+            tokio::time::sleep(QUIESCENCE_POLL_INTERVAL.min(deadline - now)).await;
+        }
     }
 
-    /// Collect services from specified namespaces
-    pub async fn collect_services(&self, namespaces: &[String]) -> Result<Vec<Value>> {
-        let mut all_services = Vec::new();
+    /// List workloads whose rollout has not finished, as "Kind namespace/name"
+    async fn list_rolling_workloads(&self, namespaces: &[String]) -> Vec<String> {
+        let mut rolling = Vec::new();
 
         for namespace in namespaces {
-            info!("Collecting services from namespace: {}", namespace);
-            let services: Api<Service> = Api::namespaced(self.client.clone(), namespace);
-
-            match services.list(&Default::default()).await {
-                Ok(service_list) => {
-                    let service_count = service_list.items.len();
-                    for service in service_list.items {
-                        if let Ok(json) = serde_json::to_value(&service) {
-                            all_services.push(json);
-                        }
-                    }
-                    info!(
-                        "Found {} services in namespace {}",
-                        service_count, namespace
-                    );
-                }
+            let deployments: Api<Deployment> = Api::namespaced(self.client.clone(), namespace);
+            self.rate_limiter.acquire("deployments").await;
+            let deployments = match deployments.list(&Default::default()).await {
+                Ok(list) => list.items,
                 Err(e) => {
                     warn!(
-                        "Failed to collect services from namespace {}: {}",
+                        "Failed to check deployments in namespace {}: {}",
                         namespace, e
                     );
+                    Vec::new()
+                }
+            };
+            for deployment in deployments {
+                let desired = deployment
+                    .spec
+                    .as_ref()
+                    .and_then(|s| s.replicas)
+                    .unwrap_or(1);
+                let generation = deployment.metadata.generation.unwrap_or_default();
+                let status = deployment.status.unwrap_or_default();
+                if status.updated_replicas.unwrap_or_default() != desired
+                    || status.replicas.unwrap_or_default() != desired
+                    || status.observed_generation.unwrap_or_default() < generation
+                {
+                    rolling.push(format!(
+                        "Deployment {}/{}",
+                        namespace,
+                        deployment.metadata.name.unwrap_or_default()
+                    ));
                 }
             }
-        }
-
-        Ok(all_services)
-    }
 
-    /// Collect deployments from specified namespaces
-    pub async fn collect_deployments(&self, namespaces: &[String]) -> Result<Vec<Value>> {
-        let mut all_deployments = Vec::new();
-
-        for namespace in namespaces {
-            info!("Collecting deployments from namespace: {}", namespace);
-            let deployments: Api<Deployment> = Api::namespaced(self.client.clone(), namespace);
-
-            match deployments.list(&Default::default()).await {
-                Ok(deployment_list) => {
-                    let deployment_count = deployment_list.items.len();
-                    for deployment in deployment_list.items {
-                        if let Ok(json) = serde_json::to_value(&deployment) {
-                            all_deployments.push(json);
-                        }
-                    }
-                    info!(
-                        "Found {} deployments in namespace {}",
-                        deployment_count, namespace
+            let statefulsets: Api<StatefulSet> = Api::namespaced(self.client.clone(), namespace);
+            self.rate_limiter.acquire("statefulsets").await;
+            let statefulsets = match statefulsets.list(&Default::default()).await {
+                Ok(list) => list.items,
+                Err(e) => {
+                    warn!(
+                        "Failed to check statefulsets in namespace {}: {}",
+                        namespace, e
                     );
+                    Vec::new()
                 }
+            };
+            for statefulset in statefulsets {
+                let desired = statefulset
+                    .spec
+                    .as_ref()
+                    .and_then(|s| s.replicas)
+                    .unwrap_or(1);
+                let status = statefulset.status.unwrap_or_default();
+                if status.updated_replicas.unwrap_or_default() != desired {
+                    rolling.push(format!(
+                        "StatefulSet {}/{}",
+                        namespace,
+                        statefulset.metadata.name.unwrap_or_default()
+                    ));
+                }
+            }
+
+            let daemonsets: Api<DaemonSet> = Api::namespaced(self.client.clone(), namespace);
+            self.rate_limiter.acquire("daemonsets").await;
+            let daemonsets = match daemonsets.list(&Default::default()).await {
+                Ok(list) => list.items,
                 Err(e) => {
                     warn!(
-                        "Failed to collect deployments from namespace {}: {}",
+                        "Failed to check daemonsets in namespace {}: {}",
                         namespace, e
                     );
+                    Vec::new()
+                }
+            };
+            for daemonset in daemonsets {
+                let status = daemonset.status.unwrap_or_default();
+                if status.updated_number_scheduled.unwrap_or_default()
+                    != status.desired_number_scheduled
+                {
+                    rolling.push(format!(
+                        "DaemonSet {}/{}",
+                        namespace,
+                        daemonset.metadata.name.unwrap_or_default()
+                    ));
                 }
             }
         }
 
-        Ok(all_deployments)
+        rolling
     }
 
-    /// Collect configmaps from specified namespaces
-    pub async fn collect_configmaps(&self, namespaces: &[String]) -> Result<Vec<Value>> {
-        let mut all_configmaps = Vec::new();
+    /// Collect a namespaced resource type from specified namespaces, falling back to an
+    /// unfiltered list where the API server rejects the field selector for this type
+    async fn collect_resources<K>(
+        &self,
+        namespaces: &[String],
+        resource_name: &str,
+        field_selector: Option<&str>,
+    ) -> Result<Vec<Value>>
+    where
+        K: Resource<Scope = NamespaceResourceScope, DynamicType = ()>
+            + Clone
+            + DeserializeOwned
+            + Serialize
+            + Debug,
+    {
+        self.list_in_namespaces(namespaces, resource_name, field_selector, |namespace| {
+            Api::<K>::namespaced(self.client.clone(), namespace)
+        })
+        .await
+    }
 
-        for namespace in namespaces {
-            info!("Collecting configmaps from namespace: {}", namespace);
-            let configmaps: Api<ConfigMap> = Api::namespaced(self.client.clone(), namespace);
-
-            match configmaps.list(&Default::default()).await {
-                Ok(configmap_list) => {
-                    let configmap_count = configmap_list.items.len();
-                    for configmap in configmap_list.items {
-                        if let Ok(json) = serde_json::to_value(&configmap) {
-                            all_configmaps.push(json);
-                        }
-                    }
-                    info!(
-                        "Found {} configmaps in namespace {}",
-                        configmap_count, namespace
-                    );
+    /// Collect a namespaced custom resource type, listed dynamically as it has no k8s-openapi
+    /// type. Items get apiVersion and kind set, as list responses may omit them
+    async fn collect_dynamic_resources(
+        &self,
+        namespaces: &[String],
+        resource: &ApiResource,
+    ) -> Result<Vec<Value>> {
+        let mut resources = self
+            .list_in_namespaces(namespaces, &resource.plural, None, |namespace| {
+                Api::<DynamicObject>::namespaced_with(self.client.clone(), namespace, resource)
+            })
+            .await?;
+        for item in &mut resources {
+            item["apiVersion"] = resource.api_version.clone().into();
+            item["kind"] = resource.kind.clone().into();
+        }
+        Ok(resources)
+    }
+
+    /// List a resource type in each namespace through the API built by api_for, recording
+    /// failed lists instead of aborting
+    async fn list_in_namespaces<K>(
+        &self,
+        namespaces: &[String],
+        resource_name: &str,
+        field_selector: Option<&str>,
+        api_for: impl Fn(&str) -> Api<K>,
+    ) -> Result<Vec<Value>>
+    where
+        K: Clone + DeserializeOwned + Serialize + Debug,
+    {
+        let api_for = &api_for;
+        let started = Instant::now();
+        let results: Vec<(Vec<Value>, Duration)> = stream::iter(namespaces)
+            .map(|namespace| async move {
+                if interrupt::is_interrupted() {
+                    return (Vec::new(), Duration::ZERO);
                 }
-                Err(e) => {
+
+                info!("Collecting {} from namespace: {}", resource_name, namespace);
+                let api = api_for(namespace);
+                let mut namespace_resources = Vec::new();
+
+                let mut params = ListParams::default();
+                if let Some(field_selector) = field_selector {
+                    params = params.fields(field_selector);
+                }
+
+                self.rate_limiter.acquire(resource_name).await;
+                let requested = Instant::now();
+                let mut result = api.list(&params).await;
+                if let (Some(field_selector), Err(kube::Error::Api(response))) =
+                    (field_selector, &result)
+                    && response.code == 400
+                {
                     warn!(
-                        "Failed to collect configmaps from namespace {}: {}",
-                        namespace, e
+                        "Field selector '{}' is not supported for {} ({}), collecting them unfiltered",
+                        field_selector, resource_name, response.message
                     );
+                    self.rate_limiter.acquire(resource_name).await;
+                    result = api.list(&Default::default()).await;
                 }
+
+                match result {
+                    Ok(resource_list) => {
+                        let resource_count = resource_list.items.len();
+                        for resource in resource_list.items {
+                            if let Ok(json) = serde_json::to_value(&resource) {
+                                namespace_resources.push(json);
+                            }
+                        }
+                        info!(
+                            "Found {} {} in namespace {}",
+                            resource_count, resource_name, namespace
+                        );
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to collect {} from namespace {}: {}",
+                            resource_name, namespace, e
+                        );
+                        self.record_failed_list(namespace, resource_name);
+                    }
+                }
+
+                (namespace_resources, requested.elapsed())
+            })
+            .buffered(self.namespace_concurrency)
+            .collect()
+            .await;
+
+        self.record_timing(
+            resource_name,
+            started.elapsed(),
+            results.iter().map(|(_, request)| *request).sum(),
+            results.len(),
+        );
+        let mut resources: Vec<Value> = results
+            .into_iter()
+            .flat_map(|(resources, _)| resources)
+            .collect();
+        sort_by_namespace_and_name(&mut resources);
+        Ok(resources)
+    }
+
+    /// Collect a cluster-scoped resource type
+    async fn collect_cluster_resources<K>(&self, resource_name: &str) -> Result<Vec<Value>>
+    where
+        K: Resource<Scope = ClusterResourceScope, DynamicType = ()>
+            + Clone
+            + DeserializeOwned
+            + Serialize
+            + Debug,
+    {
+        info!("Collecting cluster-scoped {}", resource_name);
+        let api: Api<K> = Api::all(self.client.clone());
+        let mut all_resources = Vec::new();
+
+        let started = Instant::now();
+        self.rate_limiter.acquire(resource_name).await;
+        let requested = Instant::now();
+        let result = api.list(&Default::default()).await;
+        self.record_timing(resource_name, started.elapsed(), requested.elapsed(), 1);
+        match result {
+            Ok(resource_list) => {
+                for resource in resource_list.items {
+                    if let Ok(json) = serde_json::to_value(&resource) {
+                        all_resources.push(json);
+                    }
+                }
+                info!("Found {} {}", all_resources.len(), resource_name);
+            }
+            Err(e) => {
+                warn!("Failed to collect {}: {}", resource_name, e);
             }
         }
 
-        Ok(all_configmaps)
+        sort_by_namespace_and_name(&mut all_resources);
+        Ok(all_resources)
+    }
+
+    /// Collect pods from specified namespaces
+    pub async fn collect_pods(&self, namespaces: &[String]) -> Result<Vec<Value>> {
+        self.collect_resources::<Pod>(namespaces, "pods", self.field_selector.as_deref())
+            .await
+    }
+
+    /// Collect services from specified namespaces
+    pub async fn collect_services(&self, namespaces: &[String]) -> Result<Vec<Value>> {
+        self.collect_resources::<Service>(namespaces, "services", self.field_selector.as_deref())
+            .await
+    }
+
+    /// Collect deployments from specified namespaces
+    pub async fn collect_deployments(&self, namespaces: &[String]) -> Result<Vec<Value>> {
+        self.collect_resources::<Deployment>(
+            namespaces,
+            "deployments",
+            self.field_selector.as_deref(),
+        )
+        .await
+    }
+
+    /// Collect configmaps from specified namespaces
+    pub async fn collect_configmaps(&self, namespaces: &[String]) -> Result<Vec<Value>> {
+        self.collect_resources::<ConfigMap>(
+            namespaces,
+            "configmaps",
+            self.field_selector.as_deref(),
+        )
+        .await
     }
 
     /// Collect secrets from specified namespaces
     pub async fn collect_secrets(&self, namespaces: &[String]) -> Result<Vec<Value>> {
-        let mut all_secrets = Vec::new();
+        self.collect_resources::<Secret>(namespaces, "secrets", self.field_selector.as_deref())
+            .await
+    }
 
-        for namespace in namespaces {
-            info!("Collecting secrets from namespace: {}", namespace);
-            let secrets: Api<Secret> = Api::namespaced(self.client.clone(), namespace);
-
-            match secrets.list(&Default::default()).await {
-                Ok(secret_list) => {
-                    let secret_count = secret_list.items.len();
-                    for secret in secret_list.items {
-                        if let Ok(json) = serde_json::to_value(&secret) {
-                            all_secrets.push(json);
-                        }
-                    }
-                    info!("Found {} secrets in namespace {}", secret_count, namespace);
-                }
-                Err(e) => {
-                    warn!(
-                        "Failed to collect secrets from namespace {}: {}",
-                        namespace, e
-                    );
-                }
+    /// Collect service accounts from specified namespaces
+    pub async fn collect_serviceaccounts(&self, namespaces: &[String]) -> Result<Vec<Value>> {
+        self.collect_resources::<ServiceAccount>(
+            namespaces,
+            "serviceaccounts",
+            self.field_selector.as_deref(),
+        )
+        .await
+    }
+
+    /// Collect ingresses from specified namespaces
+    pub async fn collect_ingresses(&self, namespaces: &[String]) -> Result<Vec<Value>> {
+        self.collect_resources::<Ingress>(namespaces, "ingresses", self.field_selector.as_deref())
+            .await
+    }
+
+    /// Collect K3s/RKE2 helm.cattle.io HelmCharts from specified namespaces, or none when the
+    /// CRD is not installed
+    pub async fn collect_helmcharts(&self, namespaces: &[String]) -> Result<Vec<Value>> {
+        let Some(version) = self.crd_version("helmcharts.helm.cattle.io", "v1").await? else {
+            return Ok(Vec::new());
+        };
+        self.collect_dynamic_resources(
+            namespaces,
+            &custom_resource("helm.cattle.io", &version, "HelmChart", "helmcharts"),
+        )
+        .await
+    }
+
+    /// Collect K3s/RKE2 helm.cattle.io HelmChartConfigs from specified namespaces, or none when
+    /// the CRD is not installed
+    pub async fn collect_helmchartconfigs(&self, namespaces: &[String]) -> Result<Vec<Value>> {
+        let Some(version) = self
+            .crd_version("helmchartconfigs.helm.cattle.io", "v1")
+            .await?
+        else {
+            return Ok(Vec::new());
+        };
+        self.collect_dynamic_resources(
+            namespaces,
+            &custom_resource(
+                "helm.cattle.io",
+                &version,
+                "HelmChartConfig",
+                "helmchartconfigs",
+            ),
+        )
+        .await
+    }
+
+    /// Collect resource quotas from specified namespaces
+    pub async fn collect_resourcequotas(&self, namespaces: &[String]) -> Result<Vec<Value>> {
+        self.collect_resources::<ResourceQuota>(
+            namespaces,
+            "resourcequotas",
+            self.field_selector.as_deref(),
+        )
+        .await
+    }
+
+    /// Collect DaemonSets from the system namespaces, used to detect the CNI and ingress
+    pub async fn collect_system_daemonsets(&self) -> Result<Vec<Value>> {
+        self.collect_resources::<DaemonSet>(&system_namespaces(), "system-daemonsets", None)
+            .await
+    }
+
+    /// Collect Deployments from the system namespaces, used to detect the ingress controller
+    pub async fn collect_system_deployments(&self) -> Result<Vec<Value>> {
+        self.collect_resources::<Deployment>(&system_namespaces(), "system-deployments", None)
+            .await
+    }
+
+    /// Collect StatefulSets from the system namespaces, used to detect Prometheus
+    pub async fn collect_system_statefulsets(&self) -> Result<Vec<Value>> {
+        self.collect_resources::<StatefulSet>(&system_namespaces(), "system-statefulsets", None)
+            .await
+    }
+
+    /// Whether a CustomResourceDefinition is installed, treating lookup failures as absent
+    pub async fn crd_exists(&self, name: &str) -> Result<bool> {
+        let api: Api<CustomResourceDefinition> = Api::all(self.client.clone());
+
+        self.rate_limiter.acquire("customresourcedefinitions").await;
+        match api.get_opt(name).await {
+            Ok(crd) => Ok(crd.is_some()),
+            Err(e) => {
+                warn!("Failed to look up CustomResourceDefinition {}: {}", name, e);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Version to list a custom resource at, or None when its CRD is not installed: the
+    /// --prefer-api-version choice for its group if served, else `default` if served, else the
+    /// CRD's storage version
+    pub async fn crd_version(&self, name: &str, default: &str) -> Result<Option<String>> {
+        let api: Api<CustomResourceDefinition> = Api::all(self.client.clone());
+
+        self.rate_limiter.acquire("customresourcedefinitions").await;
+        let crd = match api.get_opt(name).await {
+            Ok(Some(crd)) => crd,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                warn!("Failed to look up CustomResourceDefinition {}: {}", name, e);
+                return Ok(None);
+            }
+        };
+
+        let served: Vec<&str> = crd
+            .spec
+            .versions
+            .iter()
+            .filter(|version| version.served)
+            .map(|version| version.name.as_str())
+            .collect();
+        if let Some(preferred) = self.preferred_versions.get(&crd.spec.group) {
+            if served.contains(&preferred.as_str()) {
+                return Ok(Some(preferred.clone()));
+            }
+            warn!(
+                "{} does not serve the preferred version {} (served: {}), using its default",
+                name,
+                preferred,
+                served.join(", ")
+            );
+        }
+        if served.contains(&default) {
+            return Ok(Some(default.to_string()));
+        }
+        let storage = crd
+            .spec
+            .versions
+            .iter()
+            .find(|version| version.storage)
+            .map_or(default, |version| version.name.as_str());
+        Ok(Some(storage.to_string()))
+    }
+
+    /// Collect Namespace objects, used for their labels rather than saved
+    pub async fn collect_namespaces(&self) -> Result<Vec<Value>> {
+        self.collect_cluster_resources::<Namespace>("namespaces")
+            .await
+    }
+
+    /// Collect all ClusterRoles in the cluster
+    pub async fn collect_clusterroles(&self) -> Result<Vec<Value>> {
+        self.collect_cluster_resources::<ClusterRole>("clusterroles")
+            .await
+    }
+
+    /// Collect all ClusterRoleBindings in the cluster
+    pub async fn collect_clusterrolebindings(&self) -> Result<Vec<Value>> {
+        self.collect_cluster_resources::<ClusterRoleBinding>("clusterrolebindings")
+            .await
+    }
+
+    /// Collect Roles from specified namespaces
+    pub async fn collect_roles(&self, namespaces: &[String]) -> Result<Vec<Value>> {
+        self.collect_resources::<Role>(namespaces, "roles", None)
+            .await
+    }
+
+    /// Collect RoleBindings from specified namespaces
+    pub async fn collect_rolebindings(&self, namespaces: &[String]) -> Result<Vec<Value>> {
+        self.collect_resources::<RoleBinding>(namespaces, "rolebindings", None)
+            .await
+    }
+
+    /// Collect all mutating and validating admission webhook configurations in the cluster
+    pub async fn collect_webhook_configurations(&self) -> Result<Vec<Value>> {
+        let mut configurations = self
+            .collect_cluster_resources::<MutatingWebhookConfiguration>(
+                "mutatingwebhookconfigurations",
+            )
+            .await?;
+        configurations.extend(
+            self.collect_cluster_resources::<ValidatingWebhookConfiguration>(
+                "validatingwebhookconfigurations",
+            )
+            .await?,
+        );
+        Ok(configurations)
+    }
+
+    /// Collect all APIServices, whose aggregated APIs are served by in-cluster Services
+    pub async fn collect_apiservices(&self) -> Result<Vec<Value>> {
+        self.collect_cluster_resources::<APIService>("apiservices")
+            .await
+    }
+
+    /// Collect all nodes in the cluster
+    pub async fn collect_nodes(&self) -> Result<Vec<Value>> {
+        self.collect_cluster_resources::<Node>("nodes").await
+    }
+
+    /// Collect PodDisruptionBudgets from specified namespaces
+    pub async fn collect_poddisruptionbudgets(&self, namespaces: &[String]) -> Result<Vec<Value>> {
+        self.collect_resources::<PodDisruptionBudget>(namespaces, "poddisruptionbudgets", None)
+            .await
+    }
+
+    /// Collect persistent volume claims from specified namespaces
+    pub async fn collect_persistentvolumeclaims(
+        &self,
+        namespaces: &[String],
+    ) -> Result<Vec<Value>> {
+        self.collect_resources::<PersistentVolumeClaim>(namespaces, "persistentvolumeclaims", None)
+            .await
+    }
+
+    /// Collect all persistent volumes in the cluster
+    pub async fn collect_persistentvolumes(&self) -> Result<Vec<Value>> {
+        self.collect_cluster_resources::<PersistentVolume>("persistentvolumes")
+            .await
+    }
+
+    /// Collect NodeMetrics from metrics.k8s.io. Fails if metrics-server is not installed.
+    pub async fn collect_node_metrics(&self) -> Result<Vec<Value>> {
+        let api: Api<DynamicObject> = Api::all_with(
+            self.client.clone(),
+            &metrics_resource("NodeMetrics", "nodes"),
+        );
+        self.rate_limiter.acquire("node metrics").await;
+        let list = api
+            .list(&Default::default())
+            .await
+            .context("Failed to list node metrics")?;
+
+        Ok(list
+            .items
+            .iter()
+            .filter_map(|item| serde_json::to_value(item).ok())
+            .collect())
+    }
+
+    /// Collect the Rancher Backup Operator's cluster-scoped resources.cattle.io Backups, or None
+    /// when the operator's CRDs are not installed
+    pub async fn collect_rancher_backups(&self) -> Result<Option<Vec<Value>>> {
+        let Some(version) = self
+            .crd_version("backups.resources.cattle.io", "v1")
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let api: Api<DynamicObject> = Api::all_with(
+            self.client.clone(),
+            &custom_resource("resources.cattle.io", &version, "Backup", "backups"),
+        );
+        self.rate_limiter.acquire("backups").await;
+        match api.list(&Default::default()).await {
+            Ok(list) => {
+                let backups: Vec<Value> = list
+                    .items
+                    .iter()
+                    .filter_map(|item| serde_json::to_value(item).ok())
+                    .collect();
+                info!("Found {} Rancher Backups", backups.len());
+                Ok(Some(backups))
+            }
+            Err(e) => {
+                warn!("Failed to collect Rancher Backups: {}", e);
+                Ok(Some(Vec::new()))
             }
         }
+    }
+
+    /// Collect Gatekeeper ConstraintTemplates and the constraints created from them, or None
+    /// when Gatekeeper is not installed
+    pub async fn collect_gatekeeper_policies(&self) -> Result<Option<(Vec<Value>, Vec<Value>)>> {
+        let Some(version) = self
+            .crd_version("constrainttemplates.templates.gatekeeper.sh", "v1")
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let api: Api<DynamicObject> = Api::all_with(
+            self.client.clone(),
+            &custom_resource(
+                "templates.gatekeeper.sh",
+                &version,
+                "ConstraintTemplate",
+                "constrainttemplates",
+            ),
+        );
+        self.rate_limiter.acquire("constrainttemplates").await;
+        let templates: Vec<Value> = match api.list(&Default::default()).await {
+            Ok(list) => list
+                .items
+                .iter()
+                .filter_map(|item| serde_json::to_value(item).ok())
+                .collect(),
+            Err(e) => {
+                warn!("Failed to collect Gatekeeper ConstraintTemplates: {}", e);
+                return Ok(None);
+            }
+        };
+
+        // Each template defines a cluster-scoped constraint kind whose plural is its lowercase name
+        let constraint_version = self
+            .preferred_versions
+            .get("constraints.gatekeeper.sh")
+            .map_or("v1beta1", String::as_str);
+        let mut constraints = Vec::new();
+        for kind in templates.iter().filter_map(|t| {
+            t.pointer("/spec/crd/spec/names/kind")
+                .and_then(|k| k.as_str())
+        }) {
+            let api: Api<DynamicObject> = Api::all_with(
+                self.client.clone(),
+                &custom_resource(
+                    "constraints.gatekeeper.sh",
+                    constraint_version,
+                    kind,
+                    &kind.to_lowercase(),
+                ),
+            );
+            self.rate_limiter.acquire("constraints").await;
+            match api.list(&Default::default()).await {
+                Ok(list) => constraints.extend(list.items.iter().filter_map(|item| {
+                    let mut constraint = serde_json::to_value(item).ok()?;
+                    constraint["kind"] = kind.into();
+                    Some(constraint)
+                })),
+                Err(e) => warn!("Failed to collect {} constraints: {}", kind, e),
+            }
+        }
+
+        info!(
+            "Found {} Gatekeeper ConstraintTemplates and {} constraints",
+            templates.len(),
+            constraints.len()
+        );
+        Ok(Some((templates, constraints)))
+    }
+
+    /// Collect PodMetrics from metrics.k8s.io for the given namespaces
+    pub async fn collect_pod_metrics(&self, namespaces: &[String]) -> Result<Vec<Value>> {
+        let resource = metrics_resource("PodMetrics", "pods");
+        let mut metrics = Vec::new();
 
-        Ok(all_secrets)
+        for namespace in namespaces {
+            let api: Api<DynamicObject> =
+                Api::namespaced_with(self.client.clone(), namespace, &resource);
+            self.rate_limiter.acquire("pod metrics").await;
+            let list = api
+                .list(&Default::default())
+                .await
+                .with_context(|| format!("Failed to list pod metrics in {}", namespace))?;
+            metrics.extend(
+                list.items
+                    .iter()
+                    .filter_map(|item| serde_json::to_value(item).ok()),
+            );
+        }
+
+        Ok(metrics)
+    }
+
+    /// Collect all storage classes in the cluster
+    pub async fn collect_storageclasses(&self) -> Result<Vec<Value>> {
+        self.collect_cluster_resources::<StorageClass>("storageclasses")
+            .await
+    }
+
+    /// Collect all CustomResourceDefinitions, i.e. the schemas of custom resources but none of
+    /// their instances
+    pub async fn collect_customresourcedefinitions(&self) -> Result<Vec<Value>> {
+        self.collect_cluster_resources::<CustomResourceDefinition>("customresourcedefinitions")
+            .await
     }
 }
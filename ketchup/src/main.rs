@@ -1,40 +1,295 @@
-use anyhow::Result;
-use clap::Parser;
-use output::OutputManager;
+use anyhow::{Context, Result};
+use clap::builder::RangedU64ValueParser;
+use clap::error::ErrorKind;
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand};
+use futures::{FutureExt, StreamExt, stream};
+use output::{
+    CLUSTER_SCOPED_DIR, ClusterSummary, FleetClusterResult, NamespaceStats, OutputManager,
+    RedactionCounts, ResourceCounts, SecretKeyCounts, SkippedResources,
+};
+use regex::Regex;
 use serde_json::Value;
-use tracing::info;
+use state::CollectionState;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tracing::{debug, info, warn};
 
+mod analysis;
+mod diff;
+mod fleet;
+mod interrupt;
 mod k8s;
 mod output;
+mod state;
+mod throttle;
+
+const SERVICE_ACCOUNT_TOKEN_SECRET: &str = "kubernetes.io/service-account-token";
+const HELM_RELEASE_SECRET: &str = "helm.sh/release.v1";
+
+/// Default permissions for written files and created directories
+const DEFAULT_FILE_MODE: u32 = 0o600;
+const DEFAULT_DIR_MODE: u32 = 0o700;
+
+/// Recommend --exclude-system-secrets in the summary once this many token secrets are seen
+const SYSTEM_SECRET_NOTE_THRESHOLD: usize = 10;
+
+/// Control-plane ConfigMaps copied to cluster-config/ for restore planning
+const BOOTSTRAP_NAMESPACE: &str = "kube-system";
+const BOOTSTRAP_CONFIGMAPS: &[&str] = &["kubeadm-config", "cluster-info", "kube-proxy"];
 
 #[derive(Parser, Debug)]
 #[command(name = "ketchup")]
 #[command(about = "Collect Kubernetes cluster configurations")]
 #[command(version)]
+#[command(subcommand_negates_reqs = true)]
+#[command(group(ArgGroup::new("token_source").args(["token", "token_file"])))]
 struct Args {
-    /// Path to kubeconfig file (required)
-    #[arg(short, long)]
-    kubeconfig: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Path to kubeconfig file (required unless --fleet or --server is used)
+    #[arg(short, long, required_unless_present_any = ["fleet", "server"])]
+    kubeconfig: Option<String>,
+
+    /// API server URL to connect to with --token or --token-file instead of a kubeconfig
+    #[arg(long, conflicts_with_all = ["kubeconfig", "fleet"], requires = "token_source")]
+    server: Option<String>,
+
+    /// Bearer token for --server
+    #[arg(long, requires = "server")]
+    token: Option<String>,
+
+    /// File containing the bearer token for --server
+    #[arg(long, requires = "server")]
+    token_file: Option<String>,
+
+    /// CA certificate file used to verify --server, or trusted in addition to the kubeconfig
+    /// cluster's own CA (or the system CAs when it has none)
+    #[arg(long)]
+    ca_file: Option<String>,
+
+    /// Do not verify the TLS certificate of --server
+    #[arg(long, requires = "server", conflicts_with = "ca_file")]
+    insecure_skip_tls_verify: bool,
+
+    /// Fleet file listing clusters (name, kubeconfig, context) to collect one after another
+    #[arg(long, conflicts_with = "kubeconfig")]
+    fleet: Option<String>,
 
     /// Namespaces to collect from (comma-separated)
     #[arg(short, long)]
     namespaces: Option<String>,
 
+    /// Only collect resources matching this field selector (e.g. status.phase=Running); types
+    /// that do not support it are collected unfiltered
+    #[arg(long)]
+    field_selector: Option<String>,
+
+    /// List custom resources of an API group at this version when its CRD serves it
+    /// (repeatable), e.g. helm.cattle.io=v1; otherwise the built-in version is used
+    #[arg(long, value_name = "GROUP=VERSION", value_parser = parse_api_version_preference)]
+    prefer_api_version: Vec<(String, String)>,
+
+    /// Only save resources whose metadata.name matches this regular expression
+    #[arg(long, value_name = "REGEX")]
+    name_filter: Option<Regex>,
+
     /// Output directory for the archive
     #[arg(short, long, default_value = "/tmp")]
     output: String,
 
-    /// Output format: json, yaml, or both
-    #[arg(short, long, default_value = "yaml", value_parser = ["json", "yaml", "both"])]
+    /// Output format: json, yaml, both, or all (json, yaml and ndjson)
+    #[arg(short, long, default_value = "yaml", value_parser = ["json", "yaml", "both", "all"])]
     format: String,
 
     /// Compression: compressed, uncompressed, or both
     #[arg(short = 'c', long, default_value = "compressed", value_parser = ["compressed", "uncompressed", "both"])]
     compression: String,
 
+    /// Delete the output directory once the archive is written and verified
+    #[arg(long)]
+    archive_only: bool,
+
+    /// Top-level folder inside the archive (default: the output directory's name)
+    #[arg(long, value_name = "NAME", value_parser = parse_tar_root_dir)]
+    tar_root_dir: Option<String>,
+
+    /// Serialize and write each batch of resource files on all CPUs instead of one thread
+    #[arg(long)]
+    parallel_writes: bool,
+
+    /// Write resources straight into the archive without an intermediate output directory,
+    /// halving peak disk usage
+    #[arg(long, conflicts_with = "resume")]
+    stream_archive: bool,
+
+    /// Number of resource types collected in parallel
+    #[arg(long, default_value_t = 2, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    type_concurrency: usize,
+
+    /// Number of fleet clusters collected in parallel [default: 1]
+    #[arg(long, requires = "fleet", conflicts_with_all = ["kubeconfig", "server"], value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    fleet_concurrency: Option<usize>,
+
+    /// Number of namespaces listed in parallel for each resource type
+    #[arg(long, default_value_t = 4, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    namespace_concurrency: usize,
+
+    /// Maximum list requests per second sent to the API server
+    #[arg(long, default_value_t = k8s::DEFAULT_QPS, value_parser = clap::value_parser!(u32).range(1..))]
+    qps: u32,
+
+    /// Before collecting, wait up to this long (e.g. 90s, 5m) for workload rollouts to finish
+    #[arg(long, value_parser = parse_duration)]
+    wait_for_quiescence: Option<Duration>,
+
+    /// Before collecting, check that every collected resource type may be listed and report
+    /// the denied ones
+    #[arg(long)]
+    preflight: bool,
+
+    /// Only run the --preflight checks, print the access matrix and exit (1 if any is denied)
+    #[arg(long, conflicts_with = "fleet")]
+    preflight_only: bool,
+
+    /// Remove metadata.namespace from saved resources so they can be applied to any namespace
+    #[arg(long)]
+    strip_namespace: bool,
+
+    /// Remove annotations whose keys start with any of these comma-separated prefixes from saved
+    /// resources, e.g. field.cattle.io/,meta.helm.sh/
+    #[arg(long, value_name = "PREFIXES", value_delimiter = ',')]
+    strip_annotation_prefixes: Vec<String>,
+
+    /// Add a key=value label to every saved resource (repeatable), overriding existing values
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_label)]
+    inject_label: Vec<(String, String)>,
+
+    /// Write every manifest to a single manifests/ directory as {namespace}__{kind}__{name}
+    #[arg(long)]
+    flatten: bool,
+
+    /// Manifest tree: by-namespace ({namespace}/{type}/{name}) or by-kind
+    /// (by-kind/{kind}/{namespace}__{name})
+    #[arg(long, default_value = "by-namespace", value_parser = ["by-namespace", "by-kind"])]
+    layout: String,
+
+    /// Also write every saved namespaced resource into a single v1 List (resources.yaml)
+    #[arg(long)]
+    as_list: bool,
+
+    /// Start every saved YAML manifest with a comment naming the source cluster and time
+    #[arg(long)]
+    file_header: bool,
+
+    /// Snapshot current CPU/memory usage from metrics.k8s.io into metrics/
+    #[arg(long)]
+    include_metrics: bool,
+
+    /// Also write a Markdown report (cluster-report.md) for support tickets
+    #[arg(long)]
+    emit_markdown: bool,
+
+    /// Also write a Graphviz graph (graph.dot) of owner, selector and reference edges
+    #[arg(long)]
+    emit_graph: bool,
+
+    /// Do not save Pods, their controllers recreate them on restore
+    #[arg(long)]
+    skip_pods: bool,
+
+    /// Only skip Pods with an ownerReference, keeping standalone Pods
+    #[arg(long, conflicts_with = "skip_pods")]
+    skip_owned_pods: bool,
+
+    /// Skip auto-generated ServiceAccount token secrets (regenerated by the cluster)
+    #[arg(long)]
+    exclude_system_secrets: bool,
+
+    /// Also skip Helm release secrets (helm.sh/release.v1) when excluding system secrets
+    #[arg(long, requires = "exclude_system_secrets")]
+    exclude_helm_secrets: bool,
+
+    /// Blank the private key (tls.key) of kubernetes.io/tls Secrets; certificate metadata is
+    /// still reported in tls-certificates.yaml
+    #[arg(long)]
+    redact_tls_keys: bool,
+
+    /// Replace the value at this RFC 6901 JSON Pointer with "REDACTED" in every saved resource
+    /// that has it (repeatable), e.g. /spec/credentials/password
+    #[arg(long, value_name = "POINTER", value_parser = parse_json_pointer)]
+    redact_path: Vec<String>,
+
+    /// Blank the data of every saved Secret except these comma-separated keys, e.g.
+    /// ca.crt,tls.crt for public certificates
+    #[arg(long, value_name = "KEYS", value_delimiter = ',')]
+    secret_keep_keys: Option<Vec<String>>,
+
+    /// Remove null values and empty objects and arrays from saved resources, keeping
+    /// meaningful empties such as spec, selectors and emptyDir
+    #[arg(long)]
+    prune_empty: bool,
+
+    /// Octal permissions for written files, e.g. 0640 (default 0600, Unix only)
+    #[arg(long, value_parser = parse_mode)]
+    file_mode: Option<u32>,
+
+    /// Octal permissions for created directories, e.g. 0750 (default 0700, Unix only)
+    #[arg(long, value_parser = parse_mode)]
+    dir_mode: Option<u32>,
+
+    /// Only write resources that are new or changed compared to this earlier collection directory
+    #[arg(long)]
+    only_changed_from: Option<String>,
+
+    /// Continue a failed or interrupted collection in this existing output directory,
+    /// skipping namespaces and resource types it already saved
+    #[arg(long, conflicts_with_all = ["fleet", "as_list"])]
+    resume: Option<String>,
+
+    /// Re-collect namespaces with failed lists in a second pass after the first sweep
+    #[arg(long)]
+    retry_namespaces: bool,
+
+    /// Only save CustomResourceDefinitions (cluster-wide), without namespaced resources or any
+    /// custom resource instances
+    #[arg(long, conflicts_with_all = ["resume", "only_changed_from", "retry_namespaces"])]
+    crds_only: bool,
+
     /// Verbose logging
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     verbose: bool,
+
+    /// Only log errors and print the archive (or output directory) path on success
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compare two extracted collections and report added, removed and changed resources
+    Diff {
+        /// Directory of the older collection
+        old: String,
+
+        /// Directory of the newer collection
+        new: String,
+
+        /// Directory to write diff-report.yaml to
+        #[arg(short, long, default_value = ".")]
+        output: String,
+    },
+    /// Archive an existing uncompressed collection directory into {dir}.tar.gz without
+    /// contacting the cluster
+    Compress {
+        /// Collection directory, as written with --compression uncompressed
+        dir: String,
+    },
 }
 
 #[tokio::main]
@@ -42,13 +297,248 @@ async fn main() -> Result<()> {
     let args = Args::parse();
 
     // Initialize logging
-    init_logging(args.verbose);
+    init_logging(args.verbose, args.quiet);
+
+    match &args.command {
+        Some(Command::Diff { old, new, output }) => {
+            return diff::diff_collections(old, new, output);
+        }
+        Some(Command::Compress { dir }) => return compress_collection(dir, args.quiet),
+        None => {}
+    }
+
+    for (flag, enabled) in [
+        ("--archive-only", args.archive_only),
+        ("--stream-archive", args.stream_archive),
+    ] {
+        if enabled && args.compression != "compressed" {
+            Args::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!(
+                        "{} requires --compression compressed, not {}",
+                        flag, args.compression
+                    ),
+                )
+                .exit();
+        }
+    }
+
+    if args.flatten && args.layout == "by-kind" {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--flatten cannot be combined with --layout by-kind",
+            )
+            .exit();
+    }
 
     info!("Starting Ketchup - Kubernetes Config Collector");
-    info!("Using kubeconfig: {}", args.kubeconfig);
+    if !cfg!(unix) && (args.file_mode.is_some() || args.dir_mode.is_some()) {
+        warn!("--file-mode and --dir-mode are only supported on Unix and will be ignored");
+    }
+    interrupt::install_handler();
+
+    if let Some(fleet_path) = &args.fleet {
+        collect_fleet(&args, fleet_path).await?;
+    } else {
+        let (file_mode, dir_mode) = output_modes(&args);
+        let output_manager = OutputManager::new_output_manager(args.output.clone())
+            .with_flatten(args.flatten)
+            .with_by_kind_layout(args.layout == "by-kind")
+            .with_resume_dir(args.resume.clone())
+            .with_permissions(file_mode, dir_mode)
+            .with_stream_archive(args.stream_archive)
+            .with_tar_root_dir(args.tar_root_dir.clone())
+            .with_parallel_writes(args.parallel_writes)
+            .with_file_header(header_source(&args, args.kubeconfig.as_deref(), None));
+        collect_cluster(&args, &output_manager, args.kubeconfig.as_deref(), None).await?;
+    }
+
+    if interrupt::is_interrupted() {
+        warn!("Collection was interrupted, output is partial");
+        std::process::exit(interrupt::INTERRUPTED_EXIT_CODE);
+    }
+
+    info!("Collection completed successfully");
+    Ok(())
+}
 
-    // Connect to Kubernetes using specified kubeconfig
-    let kube_client = k8s::KubeClient::new_client(&args.kubeconfig).await?;
+/// Archive a collection written earlier, checking first that it is a ketchup collection
+fn compress_collection(dir: &str, quiet: bool) -> Result<()> {
+    let dir = dir.trim_end_matches('/');
+    if !Path::new(dir).join("collection-summary.yaml").is_file() {
+        anyhow::bail!(
+            "{} is not a ketchup collection, it has no collection-summary.yaml",
+            dir
+        );
+    }
+
+    let output_manager = OutputManager::new_output_manager(dir.to_string());
+    let archive_path = output_manager.create_archive(dir)?;
+    if quiet {
+        println!("{}", archive_path);
+    }
+    Ok(())
+}
+
+/// Collect every cluster listed in a fleet file, recording failures without aborting the rest
+async fn collect_fleet(args: &Args, fleet_path: &str) -> Result<()> {
+    let clusters = fleet::load_fleet_file(fleet_path)?;
+    info!("Collecting {} clusters from fleet file", clusters.len());
+    let (file_mode, dir_mode) = output_modes(args);
+
+    // Clusters are independent, each with its own client and output directory
+    let results: Vec<FleetClusterResult> = stream::iter(&clusters)
+        .map(|cluster| collect_fleet_cluster(args, cluster, file_mode, dir_mode))
+        .buffered(args.fleet_concurrency.unwrap_or(1))
+        .collect()
+        .await;
+
+    let fleet_output_manager = OutputManager::new_output_manager(args.output.clone())
+        .with_permissions(file_mode, dir_mode);
+    fleet_output_manager.create_fleet_summary(&results)?;
+
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    if failed == results.len() {
+        anyhow::bail!("Collection failed for all {} clusters", failed);
+    }
+    if failed > 0 {
+        warn!(
+            "Fleet collection finished with {} of {} clusters failed",
+            failed,
+            results.len()
+        );
+    } else {
+        info!("Fleet collection completed successfully");
+    }
+
+    Ok(())
+}
+
+/// Collect one fleet cluster, turning errors and panics into a failed result
+async fn collect_fleet_cluster(
+    args: &Args,
+    cluster: &fleet::FleetCluster,
+    file_mode: Option<u32>,
+    dir_mode: Option<u32>,
+) -> FleetClusterResult {
+    let failed = |error: String, duration: Duration| FleetClusterResult {
+        name: cluster.name.clone(),
+        context: cluster.context.clone(),
+        output_dir: None,
+        summary: None,
+        error: Some(error),
+        duration,
+    };
+
+    if interrupt::is_interrupted() {
+        warn!("Interrupted, skipping cluster {}", cluster.name);
+        return failed("skipped after interrupt".to_string(), Duration::ZERO);
+    }
+
+    info!("Collecting cluster: {}", cluster.name);
+    let started = Instant::now();
+    let output_manager =
+        OutputManager::new_cluster_output_manager(args.output.clone(), &cluster.name)
+            .with_flatten(args.flatten)
+            .with_by_kind_layout(args.layout == "by-kind")
+            .with_permissions(file_mode, dir_mode)
+            .with_stream_archive(args.stream_archive)
+            .with_tar_root_dir(args.tar_root_dir.clone())
+            .with_parallel_writes(args.parallel_writes)
+            .with_file_header(header_source(
+                args,
+                Some(&cluster.kubeconfig),
+                cluster.context.as_deref(),
+            ));
+
+    let result = AssertUnwindSafe(collect_cluster(
+        args,
+        &output_manager,
+        Some(&cluster.kubeconfig),
+        cluster.context.as_deref(),
+    ))
+    .catch_unwind()
+    .await;
+
+    match result {
+        Ok(Ok(summary)) => FleetClusterResult {
+            name: cluster.name.clone(),
+            context: cluster.context.clone(),
+            output_dir: Some(output_manager.output_dir_path()),
+            summary: Some(summary),
+            error: None,
+            duration: started.elapsed(),
+        },
+        Ok(Err(e)) => {
+            warn!("Collection failed for cluster {}: {:#}", cluster.name, e);
+            failed(format!("{:#}", e), started.elapsed())
+        }
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            warn!(
+                "Collection panicked for cluster {}: {}",
+                cluster.name, message
+            );
+            failed(format!("panicked: {}", message), started.elapsed())
+        }
+    }
+}
+
+/// Collect a single cluster into its own timestamped output directory
+async fn collect_cluster(
+    args: &Args,
+    output_manager: &OutputManager,
+    kubeconfig: Option<&str>,
+    context: Option<&str>,
+) -> Result<ClusterSummary> {
+    // Connect to Kubernetes using the kubeconfig, or --server and a token without one
+    let kube_client = match kubeconfig {
+        Some(kubeconfig) => {
+            info!("Using kubeconfig: {}", kubeconfig);
+            k8s::KubeClient::new_client(kubeconfig, context, args.ca_file.as_deref()).await?
+        }
+        None => k8s::KubeClient::new_token_client(&token_auth(args)?).await?,
+    };
+    let kube_client = kube_client
+        .with_namespace_concurrency(args.namespace_concurrency)
+        .with_qps(args.qps)
+        .with_field_selector(args.field_selector.clone())
+        .with_preferred_versions(&args.prefer_api_version);
+
+    let kubernetes_version = match kube_client.get_server_version().await {
+        Ok(version) => version,
+        Err(e) => {
+            warn!("Could not determine Kubernetes version: {}", e);
+            "unknown".to_string()
+        }
+    };
+    info!("Kubernetes version: {}", kubernetes_version);
+
+    let cluster_id = match kube_client.get_cluster_id().await {
+        Ok(cluster_id) => cluster_id,
+        Err(e) => {
+            warn!("Could not determine cluster ID: {}", e);
+            "unknown".to_string()
+        }
+    };
+    info!("Cluster ID: {}", cluster_id);
+
+    if args.crds_only {
+        return collect_crd_schemas(
+            args,
+            output_manager,
+            &kube_client,
+            kubernetes_version,
+            cluster_id,
+        )
+        .await;
+    }
 
     // Determine which namespaces to collect from
     let requested_namespaces = if let Some(ns_str) = &args.namespaces {
@@ -59,40 +549,353 @@ async fn main() -> Result<()> {
 
     let verified_namespaces = kube_client.verify_namespaces(&requested_namespaces).await?;
     info!("Will collect from namespaces: {:?}", verified_namespaces);
+
+    if args.preflight || args.preflight_only {
+        let access = kube_client
+            .check_list_access(
+                &verified_namespaces,
+                args.wait_for_quiescence.is_some(),
+                args.include_metrics,
+            )
+            .await?;
+        let denied: Vec<&k8s::ListAccess> = access.iter().filter(|check| !check.allowed).collect();
+        for check in &denied {
+            warn!(
+                "Preflight: cannot list {} in {}{}",
+                check.resource,
+                check.namespace.as_deref().unwrap_or("the cluster"),
+                check
+                    .reason
+                    .as_ref()
+                    .map(|reason| format!(" ({})", reason))
+                    .unwrap_or_default()
+            );
+        }
+        if denied.is_empty() {
+            info!("Preflight: all {} list checks allowed", access.len());
+        } else {
+            warn!(
+                "Preflight: {} of {} list checks denied, those resources will be missing",
+                denied.len(),
+                access.len()
+            );
+        }
+
+        if args.preflight_only {
+            print!("{}", access_matrix(&access, &verified_namespaces));
+            std::process::exit(if denied.is_empty() { 0 } else { 1 });
+        }
+    }
+
     info!("Output directory: {}", args.output);
 
-    // Collect pods from verified namespaces
-    info!("Starting pod collection...");
-    let pods = kube_client.collect_pods(&verified_namespaces).await?;
-    info!("Successfully collected {} pods total", pods.len());
+    if let Some(timeout) = args.wait_for_quiescence {
+        info!(
+            "Waiting up to {}s for workloads to finish rolling out...",
+            timeout.as_secs()
+        );
+        kube_client
+            .wait_for_quiescence(&verified_namespaces, timeout)
+            .await;
+    }
 
-    // Collect services from verified namespaces
-    info!("Starting service collection...");
-    let services = kube_client.collect_services(&verified_namespaces).await?;
-    info!("Successfully collected {} services total", services.len());
+    // When resuming, namespaces whose resource types were all saved are not listed again
+    let mut collection_state = match &args.resume {
+        Some(resume_dir) => {
+            if !std::path::Path::new(resume_dir).is_dir() {
+                anyhow::bail!("--resume directory {} does not exist", resume_dir);
+            }
+            CollectionState::load(resume_dir)?
+        }
+        None => CollectionState::default(),
+    };
+    let resource_types: Vec<&str> = ResourceCounts::default()
+        .by_type()
+        .iter()
+        .map(|(resource_type, _, _)| *resource_type)
+        .collect();
+    let (resumed_namespaces, pending_namespaces): (Vec<String>, Vec<String>) = verified_namespaces
+        .iter()
+        .cloned()
+        .partition(|ns| collection_state.is_namespace_complete(ns, &resource_types));
+    if !resumed_namespaces.is_empty() {
+        info!(
+            "Resuming, skipping {} namespaces saved by the earlier run: {:?}",
+            resumed_namespaces.len(),
+            resumed_namespaces
+        );
+    }
 
-    // Collect deployments from verified namespaces
-    info!("Starting deployment collection...");
-    let deployments = kube_client
-        .collect_deployments(&verified_namespaces)
-        .await?;
+    // Collect all resource types, running up to --type-concurrency of them at once
+    info!(
+        "Starting collection (type concurrency: {}, namespace concurrency: {})...",
+        args.type_concurrency, args.namespace_concurrency
+    );
+    let mut collected =
+        NamespacedResources::collect(&kube_client, &pending_namespaces, args.type_concurrency)
+            .await?;
+
+    // Retry namespaces whose lists failed; a failed list returned nothing, so anything the retry
+    // fetches for it is new
+    let mut retried_namespaces: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let failed_lists = kube_client.take_failed_lists();
+    if args.retry_namespaces && !failed_lists.is_empty() && !interrupt::is_interrupted() {
+        for (namespace, _) in &failed_lists {
+            retried_namespaces.entry(namespace.clone()).or_default();
+        }
+        let namespaces: Vec<String> = retried_namespaces.keys().cloned().collect();
+        info!(
+            "Retrying {} namespaces with failed lists: {}",
+            namespaces.len(),
+            namespaces.join(", ")
+        );
+        let retry =
+            NamespacedResources::collect(&kube_client, &namespaces, args.type_concurrency).await?;
+        let still_failed: HashSet<(String, String)> = failed_lists
+            .intersection(&kube_client.take_failed_lists())
+            .cloned()
+            .collect();
+        for (namespace, resource_type) in failed_lists.difference(&still_failed) {
+            if let Some(recovered) = retried_namespaces.get_mut(namespace) {
+                recovered.push(resource_type.clone());
+            }
+        }
+        for recovered in retried_namespaces.values_mut() {
+            recovered.sort();
+        }
+        collected.merge_recovered(retry, &failed_lists, &still_failed);
+        kube_client.set_failed_lists(still_failed);
+    } else {
+        kube_client.set_failed_lists(failed_lists);
+    }
+
+    let NamespacedResources {
+        pods,
+        services,
+        deployments,
+        configmaps,
+        mut secrets,
+        resourcequotas,
+        serviceaccounts,
+        ingresses,
+        helmcharts,
+        helmchartconfigs,
+    } = collected;
+
+    info!("Successfully collected {} pods total", pods.len());
+    info!("Successfully collected {} services total", services.len());
     info!(
         "Successfully collected {} deployments total",
         deployments.len()
     );
-
-    // Collect ConfigMaps from verified namespaces
-    info!("Starting ConfigMap collection...");
-    let configmaps = kube_client.collect_configmaps(&verified_namespaces).await?;
     info!(
         "Successfully collected {} configmaps total",
         configmaps.len()
     );
-
-    // Collect Secrets from verified namespaces
-    info!("Starting secret collection...");
-    let secrets = kube_client.collect_secrets(&verified_namespaces).await?;
     info!("Successfully collected {} secrets total", secrets.len());
+    info!(
+        "Successfully collected {} resource quotas total",
+        resourcequotas.len()
+    );
+    info!(
+        "Successfully collected {} service accounts total",
+        serviceaccounts.len()
+    );
+    info!("Successfully collected {} ingresses total", ingresses.len());
+    info!(
+        "Successfully collected {} HelmCharts and {} HelmChartConfigs total",
+        helmcharts.len(),
+        helmchartconfigs.len()
+    );
+
+    // Node status is only needed for the inventory, nodes are not saved as manifests
+    info!("Starting node inventory collection...");
+    let nodes = kube_client.collect_nodes().await?;
+    let node_inventory = analysis::build_node_inventory(&nodes);
+    info!(
+        "Successfully collected {} nodes total",
+        node_inventory.len()
+    );
+
+    // System DaemonSets, Deployments and StatefulSets are only inspected to identify the CNI,
+    // ingress, GPU support and other add-ons
+    let system_daemonsets = kube_client.collect_system_daemonsets().await?;
+    let system_deployments = kube_client.collect_system_deployments().await?;
+    let system_statefulsets = kube_client.collect_system_statefulsets().await?;
+    let mut cni_crds = Vec::new();
+    for (crd, _) in analysis::CNI_CRDS {
+        if kube_client.crd_exists(crd).await? {
+            cni_crds.push(*crd);
+        }
+    }
+    let cni = analysis::detect_cni(&system_daemonsets, &cni_crds);
+    match &cni {
+        Some(cni) => info!("Detected CNI: {} {}", cni.name, cni.version),
+        None => info!("No known CNI DaemonSet or CRD found"),
+    }
+    let ingress = analysis::detect_ingress(&system_deployments, &system_daemonsets);
+    info!("Ingress state: {}", ingress.state);
+    let gpu = analysis::detect_gpu(
+        &system_deployments,
+        &system_daemonsets,
+        kube_client.crd_exists("clusterpolicies.nvidia.com").await?,
+        &node_inventory,
+    );
+    if let Some(gpu) = &gpu {
+        info!(
+            "Detected {} NVIDIA GPUs on {} nodes",
+            gpu.total_gpus, gpu.gpu_nodes
+        );
+    }
+
+    // Gatekeeper policies are summarized into policy-inventory.yaml, they are not saved
+    let gatekeeper = kube_client.collect_gatekeeper_policies().await?;
+    let rancher_backups = kube_client.collect_rancher_backups().await?;
+
+    // Namespace labels are only needed for the Pod Security analysis
+    let namespace_objects = kube_client.collect_namespaces().await?;
+
+    // Metrics are a diagnostic snapshot, written as reports rather than manifests
+    let metrics = if args.include_metrics {
+        info!("Collecting metrics from metrics.k8s.io...");
+        let snapshot = async {
+            let node_metrics = kube_client.collect_node_metrics().await?;
+            let pod_metrics = kube_client
+                .collect_pod_metrics(&verified_namespaces)
+                .await?;
+            anyhow::Ok((node_metrics, pod_metrics))
+        };
+        match snapshot.await {
+            Ok(metrics) => Some(metrics),
+            Err(e) => {
+                warn!("Skipping metrics, is metrics-server installed? {:#}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // RBAC objects are only needed for the RBAC aggregation and warning analyses, they are not
+    // saved
+    let cluster_roles = kube_client.collect_clusterroles().await?;
+    let cluster_role_bindings = kube_client.collect_clusterrolebindings().await?;
+    let roles = kube_client.collect_roles(&verified_namespaces).await?;
+    let role_bindings = kube_client
+        .collect_rolebindings(&verified_namespaces)
+        .await?;
+
+    // Webhook configurations and APIServices are only needed to validate their Service
+    // references, they are not saved
+    let webhook_configurations = kube_client.collect_webhook_configurations().await?;
+    let apiservices = kube_client.collect_apiservices().await?;
+
+    // PodDisruptionBudgets are only needed for the PDB analysis, they are not saved
+    let poddisruptionbudgets = kube_client
+        .collect_poddisruptionbudgets(&verified_namespaces)
+        .await?;
+
+    // Claims, volumes and classes are only needed for the storage analysis, they are not saved
+    info!("Starting storage collection...");
+    let persistentvolumeclaims = kube_client
+        .collect_persistentvolumeclaims(&verified_namespaces)
+        .await?;
+    let persistentvolumes = kube_client.collect_persistentvolumes().await?;
+    let storageclasses = kube_client.collect_storageclasses().await?;
+
+    // Drop auto-generated secrets if requested, keeping them aside as they still exist in the
+    // cluster for the reference checks
+    let mut skipped_resources = Vec::new();
+    let mut summary_notes = Vec::new();
+    let mut excluded_secrets = Vec::new();
+
+    if args.exclude_system_secrets {
+        excluded_secrets = exclude_secrets_of_type(&mut secrets, SERVICE_ACCOUNT_TOKEN_SECRET);
+        let skipped = excluded_secrets.len();
+        info!("Skipped {} ServiceAccount token secrets", skipped);
+        skipped_resources.push(SkippedResources {
+            kind: "Secret".to_string(),
+            reason: format!(
+                "type {} (--exclude-system-secrets)",
+                SERVICE_ACCOUNT_TOKEN_SECRET
+            ),
+            count: skipped,
+        });
+
+        if args.exclude_helm_secrets {
+            let helm_secrets = exclude_secrets_of_type(&mut secrets, HELM_RELEASE_SECRET);
+            let skipped = helm_secrets.len();
+            excluded_secrets.extend(helm_secrets);
+            info!("Skipped {} Helm release secrets", skipped);
+            skipped_resources.push(SkippedResources {
+                kind: "Secret".to_string(),
+                reason: format!("type {} (--exclude-helm-secrets)", HELM_RELEASE_SECRET),
+                count: skipped,
+            });
+        }
+    } else {
+        let token_secrets = secrets
+            .iter()
+            .filter(|secret| secret_type(secret) == Some(SERVICE_ACCOUNT_TOKEN_SECRET))
+            .count();
+
+        if token_secrets >= SYSTEM_SECRET_NOTE_THRESHOLD {
+            summary_notes.push(format!(
+                "{} ServiceAccount token secrets were collected; they are regenerated by the cluster, consider --exclude-system-secrets",
+                token_secrets
+            ));
+        }
+    }
+
+    // Pods left out of the saved manifests stay in the analyses
+    let saved_pods: Cow<[Value]> = if args.skip_pods || args.skip_owned_pods {
+        let kept: Vec<Value> = pods
+            .iter()
+            .filter(|pod| args.skip_owned_pods && !has_owner(pod))
+            .cloned()
+            .collect();
+        let skipped = pods.len() - kept.len();
+        let (flag, description) = if args.skip_pods {
+            ("--skip-pods", "Pods")
+        } else {
+            ("--skip-owned-pods", "Pods with an ownerReference")
+        };
+        info!("Skipped saving {} Pods ({})", skipped, flag);
+        skipped_resources.push(SkippedResources {
+            kind: "Pod".to_string(),
+            reason: format!("{} ({})", description, flag),
+            count: skipped,
+        });
+        Cow::Owned(kept)
+    } else {
+        Cow::Borrowed(&pods)
+    };
+
+    if !resumed_namespaces.is_empty() {
+        summary_notes.push(format!(
+            "Resumed collection: namespaces {} were saved by an earlier run and are not covered by this run's analysis reports",
+            resumed_namespaces.join(", ")
+        ));
+    }
+
+    for (namespace, recovered) in &retried_namespaces {
+        let outcome = if recovered.is_empty() {
+            "retry recovered no data".to_string()
+        } else {
+            format!("retry recovered {}", recovered.join(", "))
+        };
+        info!("Retried namespace {}: {}", namespace, outcome);
+        summary_notes.push(format!(
+            "Namespace {} had failed lists and was retried (--retry-namespaces): {}",
+            namespace, outcome
+        ));
+    }
+
+    if args.strip_namespace {
+        warn!(
+            "--strip-namespace removes metadata.namespace only; namespace references inside specs are not rewritten"
+        );
+    }
 
     // Create output manager and save files
     info!("Setting up file output...");
@@ -100,141 +903,1140 @@ async fn main() -> Result<()> {
         "Output format: {}, Compression: {}",
         args.format, args.compression
     );
-    let output_manager = OutputManager::new_output_manager(args.output);
     let output_dir = output_manager.create_output_directory()?;
 
+    let baseline = match &args.only_changed_from {
+        Some(baseline_dir) => {
+            info!("Loading baseline collection from {}", baseline_dir);
+            Some(diff::load_collection(baseline_dir)?)
+        }
+        None => None,
+    };
+
     // Save resources for each namespace with new structure
     let mut namespace_stats = Vec::new();
+    let mut list_items = Vec::new();
+    let mut unchanged_resources = 0;
+    let mut filtered_by_name: BTreeMap<String, usize> = BTreeMap::new();
+    let mut redactions = redaction_counts(args);
 
     for namespace in &verified_namespaces {
-        let namespace_pods: Vec<&Value> = pods
-            .iter()
-            .filter(|pod| {
-                pod.get("metadata")
-                    .and_then(|m| m.get("namespace"))
-                    .and_then(|ns| ns.as_str())
-                    == Some(namespace)
-            })
-            .collect();
+        if interrupt::is_interrupted() {
+            warn!("Interrupted, not saving namespace {}", namespace);
+            continue;
+        }
 
-        let namespace_services: Vec<&Value> = services
-            .iter()
-            .filter(|service| {
-                service
-                    .get("metadata")
-                    .and_then(|m| m.get("namespace"))
-                    .and_then(|ns| ns.as_str())
-                    == Some(namespace)
-            })
-            .collect();
+        let mut save = |resource_type: &str, resources: &[Value]| -> Result<usize> {
+            if let Some(count) = collection_state.completed_count(namespace, resource_type) {
+                debug!(
+                    "Skipping {} in namespace {}, already saved",
+                    resource_type, namespace
+                );
+                return Ok(count);
+            }
+
+            let mut namespace_resources = resources_in_namespace(resources, namespace);
+            if let Some(name_filter) = &args.name_filter {
+                namespace_resources.retain(|resource| {
+                    let matches = resource
+                        .pointer("/metadata/name")
+                        .and_then(|n| n.as_str())
+                        .is_some_and(|name| name_filter.is_match(name));
+                    if !matches {
+                        let kind = resource.get("kind").and_then(|k| k.as_str());
+                        *filtered_by_name
+                            .entry(kind.unwrap_or(resource_type).to_string())
+                            .or_default() += 1;
+                    }
+                    matches
+                });
+            }
+            for resource in &mut namespace_resources {
+                prepare_for_save(resource, args, &mut redactions);
+            }
+            if let Some(baseline) = &baseline {
+                let before = namespace_resources.len();
+                namespace_resources.retain(|resource| !diff::is_unchanged(resource, baseline));
+                unchanged_resources += before - namespace_resources.len();
+            }
+
+            let saved = output_manager.save_resources_individually(
+                &output_dir,
+                namespace,
+                resource_type,
+                &namespace_resources,
+                &args.format,
+            )?;
+            if args.as_list {
+                list_items.extend(namespace_resources);
+            }
 
-        let namespace_deployments: Vec<&Value> = deployments
+            // A failed list saved nothing, leave it for the next --resume to retry
+            if !kube_client.list_failed(namespace, resource_type) {
+                collection_state.record(namespace, resource_type, saved);
+                output_manager.save_collection_state(&output_dir, &collection_state)?;
+            }
+            Ok(saved)
+        };
+
+        namespace_stats.push(NamespaceStats {
+            namespace: namespace.clone(),
+            counts: ResourceCounts {
+                pods: save("pods", &saved_pods)?,
+                services: save("services", &services)?,
+                deployments: save("deployments", &deployments)?,
+                configmaps: save("configmaps", &configmaps)?,
+                secrets: save("secrets", &secrets)?,
+                resourcequotas: save("resourcequotas", &resourcequotas)?,
+                serviceaccounts: save("serviceaccounts", &serviceaccounts)?,
+                ingresses: save("ingresses", &ingresses)?,
+                helmcharts: save("helmcharts", &helmcharts)?,
+                helmchartconfigs: save("helmchartconfigs", &helmchartconfigs)?,
+            },
+        });
+    }
+
+    if args.as_list {
+        output_manager.save_resource_list(&output_dir, "resources", list_items, &args.format)?;
+    }
+
+    if let Some(name_filter) = &args.name_filter {
+        let kept: usize = namespace_stats
             .iter()
-            .filter(|deployment| {
-                deployment
-                    .get("metadata")
-                    .and_then(|m| m.get("namespace"))
-                    .and_then(|ns| ns.as_str())
-                    == Some(namespace)
-            })
-            .collect();
+            .map(|stats| stats.counts.total())
+            .sum();
+        let filtered: usize = filtered_by_name.values().sum();
+        info!(
+            "--name-filter '{}' kept {} resources and filtered out {}",
+            name_filter, kept, filtered
+        );
+        summary_notes.push(format!(
+            "Only resources named like '{}' were saved (--name-filter): {} kept, {} filtered out.",
+            name_filter, kept, filtered
+        ));
+        for (kind, count) in filtered_by_name {
+            skipped_resources.push(SkippedResources {
+                kind,
+                reason: "name does not match --name-filter".to_string(),
+                count,
+            });
+        }
+    }
 
-        let namespace_configmaps: Vec<Value> = configmaps
+    // Surface the bootstrap ConfigMaps among the collected ones for restore planning
+    if verified_namespaces
+        .iter()
+        .any(|ns| ns == BOOTSTRAP_NAMESPACE)
+    {
+        let mut bootstrap_configmaps: Vec<Value> = configmaps
             .iter()
             .filter(|configmap| {
                 configmap
-                    .get("metadata")
-                    .and_then(|m| m.get("namespace"))
+                    .pointer("/metadata/namespace")
                     .and_then(|ns| ns.as_str())
-                    == Some(namespace)
+                    == Some(BOOTSTRAP_NAMESPACE)
+                    && configmap
+                        .pointer("/metadata/name")
+                        .and_then(|n| n.as_str())
+                        .is_some_and(|name| BOOTSTRAP_CONFIGMAPS.contains(&name))
             })
             .cloned()
             .collect();
+        for configmap in &mut bootstrap_configmaps {
+            // Already counted when saved with their namespace
+            prepare_for_save(configmap, args, &mut RedactionCounts::default());
+        }
+        if !bootstrap_configmaps.is_empty() {
+            output_manager.save_cluster_config(&output_dir, &bootstrap_configmaps, &args.format)?;
+        }
 
-        let namespace_secrets: Vec<&Value> = secrets
+        let missing: Vec<&str> = BOOTSTRAP_CONFIGMAPS
             .iter()
-            .filter(|secret| {
-                secret
-                    .get("metadata")
-                    .and_then(|m| m.get("namespace"))
-                    .and_then(|ns| ns.as_str())
-                    == Some(namespace)
+            .copied()
+            .filter(|name| {
+                !bootstrap_configmaps.iter().any(|configmap| {
+                    configmap.pointer("/metadata/name").and_then(|n| n.as_str()) == Some(*name)
+                })
             })
             .collect();
+        // K3s and RKE2 have no kubeadm-config or kube-proxy ConfigMaps
+        if !missing.is_empty() {
+            info!(
+                "Bootstrap ConfigMaps not found in kube-system: {}",
+                missing.join(", ")
+            );
+        }
+    } else {
+        info!("kube-system is not collected, skipping cluster-config/");
+    }
+
+    // Report quota utilization from the quota status as returned by the API server
+    let quota_usage = analysis::analyze_quota_usage(&resourcequotas);
+    output_manager.write_yaml_report(&output_dir, "quota-usage.yaml", &quota_usage)?;
+    if let Some(near_limit) = quota_usage.get("near_limit").and_then(|n| n.as_array()) {
+        for entry in near_limit {
+            warn!(
+                "Namespace {} is near its quota for {}: {} of {} used",
+                entry["namespace"].as_str().unwrap_or_default(),
+                entry["resource"].as_str().unwrap_or_default(),
+                entry["used"].as_str().unwrap_or_default(),
+                entry["hard"].as_str().unwrap_or_default()
+            );
+        }
+    }
 
-        let namespace_pod_values: Vec<Value> = namespace_pods.iter().map(|&p| p.clone()).collect();
+    // Flag workloads and Ingresses whose Secrets or ConfigMaps were not collected, as restores would fail
+    let workloads: Vec<Value> = pods.iter().chain(&deployments).cloned().collect();
+    let referrers: Vec<Value> = workloads.iter().chain(&ingresses).cloned().collect();
+    let referenceable_secrets: Vec<Value> =
+        secrets.iter().chain(&excluded_secrets).cloned().collect();
+    let missing_references = analysis::find_missing_references(
+        &referrers,
+        &referenceable_secrets,
+        &configmaps,
+        &webhook_configurations,
+        &services,
+    );
+    output_manager.write_yaml_report(
+        &output_dir,
+        "missing-references.yaml",
+        &missing_references,
+    )?;
+    let restore_risks = analysis::analyze_restore_risks(
+        &webhook_configurations,
+        &apiservices,
+        &services,
+        &verified_namespaces,
+    );
+    output_manager.write_yaml_report(&output_dir, "restore-risk.yaml", &restore_risks)?;
+    let critical_risks = restore_risks["by_severity"]["critical"]
+        .as_u64()
+        .unwrap_or_default();
+    if critical_risks > 0 {
+        warn!(
+            "{} failing-closed webhooks point at Services that were not collected and would block a restore, see restore-risk.yaml",
+            critical_risks
+        );
+    }
+    output_manager.write_yaml_report(
+        &output_dir,
+        "registry-breakdown.yaml",
+        &analysis::registry_breakdown(&workloads),
+    )?;
+    output_manager.write_yaml_report(
+        &output_dir,
+        "resource-requests.yaml",
+        &analysis::analyze_resource_requests(&pods),
+    )?;
+    let pdb_analysis = analysis::analyze_pdbs(&poddisruptionbudgets, &pods, &deployments);
+    output_manager.write_yaml_report(&output_dir, "pdb-analysis.yaml", &pdb_analysis)?;
+    let blocking_pdbs = ["no_matching_pods", "zero_disruption_budget"]
+        .iter()
+        .filter_map(|issue| pdb_analysis[*issue].as_array())
+        .map(|pdbs| pdbs.len())
+        .sum::<usize>();
+    if blocking_pdbs > 0 {
+        warn!(
+            "{} PodDisruptionBudgets match no Pods or allow no disruptions and may block node drains, see pdb-analysis.yaml",
+            blocking_pdbs
+        );
+    }
+    // Finalizers and labels as collected, before any sanitization of the saved copies
+    let collected_resources = [
+        &pods,
+        &services,
+        &deployments,
+        &configmaps,
+        &secrets,
+        &resourcequotas,
+        &serviceaccounts,
+        &ingresses,
+        &helmcharts,
+        &helmchartconfigs,
+        &poddisruptionbudgets,
+    ];
+    let finalizers_report = analysis::analyze_finalizers(collected_resources.into_iter().flatten());
+    output_manager.write_yaml_report(&output_dir, "finalizers-report.yaml", &finalizers_report)?;
+    output_manager.write_yaml_report(
+        &output_dir,
+        "label-usage.yaml",
+        &analysis::analyze_label_usage(collected_resources.into_iter().flatten()),
+    )?;
+    let tls_certificates = analysis::analyze_tls_certificates(&secrets);
+    output_manager.write_yaml_report(&output_dir, "tls-certificates.yaml", &tls_certificates)?;
+    let expired_certificates = tls_certificates["expired"].as_u64().unwrap_or_default();
+    if expired_certificates > 0 {
+        warn!(
+            "{} TLS Secrets hold expired certificates, see tls-certificates.yaml",
+            expired_certificates
+        );
+    }
+    let total_missing = missing_references["total_missing"]
+        .as_u64()
+        .unwrap_or_default();
+    if total_missing > 0 {
+        warn!(
+            "{} Secret/ConfigMap/Service references point to resources that were not collected, see missing-references.yaml",
+            total_missing
+        );
+    }
 
-        let namespace_service_values: Vec<Value> =
-            namespace_services.iter().map(|&s| s.clone()).collect();
+    // Create enhanced summary
+    let storage_analysis = analysis::analyze_storage(&persistentvolumeclaims, &persistentvolumes);
+    output_manager.write_yaml_report(&output_dir, "storage-analysis.yaml", &storage_analysis)?;
+    output_manager.write_yaml_report(
+        &output_dir,
+        "pv-binding.yaml",
+        &analysis::analyze_pv_bindings(&persistentvolumes),
+    )?;
 
-        let namespace_deployment_values: Vec<Value> =
-            namespace_deployments.iter().map(|&d| d.clone()).collect();
+    let storageclass_analysis =
+        analysis::analyze_storage_classes(&storageclasses, &persistentvolumeclaims);
+    output_manager.write_yaml_report(
+        &output_dir,
+        "storageclass-analysis.yaml",
+        &storageclass_analysis,
+    )?;
+    let claims_using_default = storageclass_analysis["claims_using_default"]
+        .as_array()
+        .map_or(0, |claims| claims.len());
+    match storageclass_analysis["status"].as_str() {
+        Some("no_default") if claims_using_default > 0 => warn!(
+            "No default StorageClass but {} PVCs omit storageClassName, see storageclass-analysis.yaml",
+            claims_using_default
+        ),
+        _ => {}
+    }
+    let mut summary_warnings = Vec::new();
+    if let Some(warning) = analysis::multiple_default_classes_warning(&storageclass_analysis) {
+        warn!("{}", warning);
+        summary_warnings.push(warning);
+    }
 
-        let namespace_configmap_values: Vec<Value> =
-            namespace_configmaps.iter().map(|c| c.clone()).collect();
+    let policy_inventory = gatekeeper
+        .as_ref()
+        .map(|(templates, constraints)| analysis::analyze_policy_inventory(templates, constraints));
+    if let Some(policy_inventory) = &policy_inventory {
+        output_manager.write_yaml_report(&output_dir, "policy-inventory.yaml", policy_inventory)?;
+    }
 
-        let namespace_secret_values: Vec<Value> =
-            namespace_secrets.iter().map(|c| (*c).clone()).collect();
+    let observed_deployments: Vec<Value> = system_deployments
+        .iter()
+        .chain(&deployments)
+        .cloned()
+        .collect();
+    let mut observability = analysis::detect_opentelemetry(
+        &observed_deployments,
+        &system_daemonsets,
+        &configmaps,
+        kube_client
+            .crd_exists("opentelemetrycollectors.opentelemetry.io")
+            .await?,
+    );
+    observability["prometheus"] = analysis::detect_prometheus_stack(
+        &system_deployments,
+        &system_statefulsets,
+        kube_client
+            .crd_exists("prometheuses.monitoring.coreos.com")
+            .await?,
+        kube_client
+            .crd_exists("alertmanagers.monitoring.coreos.com")
+            .await?,
+    );
+    let management = analysis::detect_rancher_management(&system_deployments);
+    if let Some(endpoint) = management["management_endpoint"].as_str() {
+        info!("Downstream cluster managed by Rancher at {}", endpoint);
+    }
+    let backup = analysis::detect_rancher_backup(&system_deployments, rancher_backups.as_deref());
+    if management["role"] == "management" && backup["scheduled_backups"] == 0 {
+        warn!(
+            "Rancher management cluster has no scheduled Rancher Backup, see suse-edge-analysis.yaml"
+        );
+    }
+    let edge_storage = analysis::analyze_edge_storage(&storageclasses, &persistentvolumeclaims);
+    let mut virtualization_crds = Vec::new();
+    for crd in analysis::VIRTUALIZATION_CRDS {
+        if kube_client.crd_exists(crd).await? {
+            virtualization_crds.push(*crd);
+        }
+    }
+    let virtualization = analysis::detect_harvester(
+        &system_deployments,
+        &system_daemonsets,
+        &virtualization_crds,
+        edge_storage["longhorn_classes"]
+            .as_array()
+            .is_some_and(|classes| !classes.is_empty()),
+    );
+    if let Some(recommendation) = virtualization["recommendation"].as_str() {
+        info!("{}", recommendation);
+    }
+    let mut networking =
+        analysis::analyze_load_balancers(&system_deployments, &system_daemonsets, &services);
+    networking["cni"] = serde_json::json!(cni);
+    let edge_analysis = serde_json::json!({
+        "storage": edge_storage,
+        "gpu": gpu,
+        "networking": networking,
+        "management": &management,
+        "observability": observability,
+        "lifecycle": analysis::summarize_helm_charts(&helmcharts, &helmchartconfigs),
+        "backup": backup,
+        "virtualization": virtualization,
+        "infrastructure": analysis::detect_eib_provisioning(&configmaps, &secrets),
+        "security": {
+            "gatekeeper": policy_inventory.as_ref().map(|inventory| serde_json::json!({
+                "category": inventory["category"],
+                "total_templates": inventory["total_templates"],
+                "total_constraints": inventory["total_constraints"]
+            }))
+        }
+    });
+    output_manager.write_yaml_report(&output_dir, "suse-edge-analysis.yaml", &edge_analysis)?;
+    // storageclass-analysis.yaml already warns about several defaults on their own
+    if edge_analysis["storage"]["both_present"] == true
+        && let Some(recommendation) = edge_analysis["storage"]["recommendation"].as_str()
+    {
+        warn!("{}, see suse-edge-analysis.yaml", recommendation);
+    }
+    if edge_analysis["networking"]["conflict"] == true
+        && let Some(recommendation) = edge_analysis["networking"]["recommendation"].as_str()
+    {
+        warn!("{}, see suse-edge-analysis.yaml", recommendation);
+    }
 
-        let pods_saved = output_manager.save_pods_individually(
+    if let Some((node_metrics, pod_metrics)) = &metrics {
+        output_manager.write_yaml_report(
             &output_dir,
-            namespace,
-            &namespace_pod_values,
-            &args.format,
+            "metrics/nodes.yaml",
+            &serde_json::json!({ "items": node_metrics }),
         )?;
-
-        let services_saved = output_manager.save_services_individually(
+        output_manager.write_yaml_report(
             &output_dir,
-            namespace,
-            &namespace_service_values,
-            &args.format,
+            "metrics/pods.yaml",
+            &serde_json::json!({ "items": pod_metrics }),
         )?;
+    }
 
-        let deployments_saved = output_manager.save_deployments_individually(
-            &output_dir,
-            namespace,
-            &namespace_deployment_values,
-            &args.format,
-        )?;
+    let pod_security = analysis::analyze_pod_security(&namespace_objects, &verified_namespaces);
+    output_manager.write_yaml_report(&output_dir, "pod-security.yaml", &pod_security)?;
+    if let Some(privileged) = pod_security["privileged_namespaces"].as_array()
+        && !privileged.is_empty()
+    {
+        warn!(
+            "{} namespaces enforce the privileged Pod Security level, see pod-security.yaml",
+            privileged.len()
+        );
+    }
 
-        let configmaps_saved = output_manager.save_configmaps_individually(
-            &output_dir,
-            namespace,
-            &namespace_configmap_values,
-            &args.format,
-        )?;
+    output_manager.write_yaml_report(
+        &output_dir,
+        "rbac-analysis.yaml",
+        &analysis::analyze_rbac_aggregation(&cluster_roles),
+    )?;
+    let rbac_warnings = analysis::analyze_rbac_warnings(
+        &cluster_roles,
+        &roles,
+        &cluster_role_bindings,
+        &role_bindings,
+    );
+    output_manager.write_yaml_report(&output_dir, "rbac-warnings.yaml", &rbac_warnings)?;
+    let total_rbac_warnings = rbac_warnings["total_warnings"].as_u64().unwrap_or_default();
+    if total_rbac_warnings > 0 {
+        warn!(
+            "{} over-permissive RBAC rules or bindings found, see rbac-warnings.yaml",
+            total_rbac_warnings
+        );
+    }
+
+    output_manager.write_yaml_report(
+        &output_dir,
+        "node-inventory.yaml",
+        &analysis::node_inventory_report(&node_inventory),
+    )?;
+    output_manager.write_yaml_report(
+        &output_dir,
+        "scheduling-analysis.yaml",
+        &analysis::analyze_scheduling(&nodes, &workloads),
+    )?;
 
-        let secrets_saved = output_manager.save_secrets_individually(
+    if args.emit_graph {
+        let graph_resources: Vec<Value> = [
+            &pods,
+            &services,
+            &deployments,
+            &configmaps,
+            &secrets,
+            &ingresses,
+        ]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+        output_manager.write_text_report(
             &output_dir,
-            namespace,
-            &namespace_secret_values,
-            &args.format,
+            "graph.dot",
+            &analysis::dependency_graph(&graph_resources),
         )?;
+    }
+
+    // Slowest resource types first, so operators see where collection time went
+    let mut timings: Vec<(String, k8s::CollectionTiming)> =
+        kube_client.timings().into_iter().collect();
+    timings.sort_by(|(_, a), (_, b)| b.wall_seconds.total_cmp(&a.wall_seconds));
+    for (resource_name, timing) in timings.iter().take(3) {
+        info!(
+            "Slow resource type: {} took {:.2}s ({:.2}s in {} list requests)",
+            resource_name, timing.wall_seconds, timing.request_seconds, timing.lists
+        );
+    }
+    output_manager.write_yaml_report(
+        &output_dir,
+        "timing.yaml",
+        &serde_json::json!({
+            "resource_types": timings
+                .iter()
+                .map(|(resource_name, timing)| serde_json::json!({
+                    "resource": resource_name,
+                    "wall_seconds": timing.wall_seconds,
+                    "request_seconds": timing.request_seconds,
+                    "lists": timing.lists
+                }))
+                .collect::<Vec<_>>()
+        }),
+    )?;
+
+    let mut summary = ClusterSummary::new(kubernetes_version, namespace_stats);
+    summary.cluster_id = cluster_id;
+    summary.interrupted = interrupt::is_interrupted();
+    summary.nodes = node_inventory;
+    summary.cni = cni;
+    summary.ingress = Some(ingress);
+    summary.gpu = gpu;
+    summary.rancher_role = edge_analysis["management"]["role"]
+        .as_str()
+        .map(str::to_string);
+    if edge_analysis["backup"]["backup_crd"] == true {
+        summary.scheduled_backups = edge_analysis["backup"]["scheduled_backups"]
+            .as_u64()
+            .map(|count| count as usize);
+    }
+    summary.resource_usage = metrics
+        .as_ref()
+        .map(|(node_metrics, _)| analysis::summarize_node_metrics(node_metrics));
+    if baseline.is_some() {
+        summary.unchanged = Some(unchanged_resources);
+    }
+    summary.skipped = skipped_resources;
+    summary.notes = summary_notes;
+    summary.warnings = summary_warnings;
+    summary.redactions = redactions;
+    finish_collection(args, output_manager, &output_dir, &summary)?;
+    Ok(summary)
+}
+
+/// Save only the cluster's CustomResourceDefinitions for --crds-only
+async fn collect_crd_schemas(
+    args: &Args,
+    output_manager: &OutputManager,
+    kube_client: &k8s::KubeClient,
+    kubernetes_version: String,
+    cluster_id: String,
+) -> Result<ClusterSummary> {
+    info!("Collecting CustomResourceDefinitions only (--crds-only)...");
+    let mut crds = kube_client.collect_customresourcedefinitions().await?;
+    let mut filtered = 0;
+    if let Some(name_filter) = &args.name_filter {
+        let before = crds.len();
+        crds.retain(|crd| {
+            crd.pointer("/metadata/name")
+                .and_then(|n| n.as_str())
+                .is_some_and(|name| name_filter.is_match(name))
+        });
+        filtered = before - crds.len();
+    }
+    let mut redactions = redaction_counts(args);
+    for crd in &mut crds {
+        prepare_for_save(crd, args, &mut redactions);
+    }
+
+    let output_dir = output_manager.create_output_directory()?;
+    let saved = output_manager.save_resources_individually(
+        &output_dir,
+        CLUSTER_SCOPED_DIR,
+        "customresourcedefinitions",
+        &crds,
+        &args.format,
+    )?;
+    if args.as_list {
+        output_manager.save_resource_list(&output_dir, "resources", crds, &args.format)?;
+    }
 
-        namespace_stats.push((
-            namespace.clone(),
-            pods_saved,
-            services_saved,
-            deployments_saved,
-            configmaps_saved,
-            secrets_saved,
+    let mut summary = ClusterSummary::new(kubernetes_version, Vec::new());
+    summary.cluster_id = cluster_id;
+    summary.interrupted = interrupt::is_interrupted();
+    summary.crd_schemas = Some(saved);
+    summary.redactions = redactions;
+    summary.notes.push(format!(
+        "Only CustomResourceDefinition schemas were captured (--crds-only): {} CRDs, no namespaced resources or custom resource instances",
+        saved
+    ));
+    if let Some(name_filter) = &args.name_filter {
+        info!(
+            "--name-filter '{}' kept {} CustomResourceDefinitions and filtered out {}",
+            name_filter, saved, filtered
+        );
+        summary.notes.push(format!(
+            "Only resources named like '{}' were saved (--name-filter): {} kept, {} filtered out.",
+            name_filter, saved, filtered
         ));
+        if filtered > 0 {
+            summary.skipped.push(SkippedResources {
+                kind: "CustomResourceDefinition".to_string(),
+                reason: "name does not match --name-filter".to_string(),
+                count: filtered,
+            });
+        }
     }
+    finish_collection(args, output_manager, &output_dir, &summary)?;
+    Ok(summary)
+}
 
-    // Create enhanced summary
-    output_manager.create_enhanced_summary(&output_dir, &namespace_stats)?;
+/// Write the summary and optional Markdown report, then compress the output directory
+fn finish_collection(
+    args: &Args,
+    output_manager: &OutputManager,
+    output_dir: &str,
+    summary: &ClusterSummary,
+) -> Result<()> {
+    output_manager.create_enhanced_summary(output_dir, summary)?;
+
+    if args.emit_markdown {
+        output_manager.create_markdown_report(output_dir, summary)?;
+    }
 
     // Handle compression based on user preference
-    if let Some(archive_path) = output_manager.handle_compression(&output_dir, &args.compression)? {
+    let archive_path = output_manager.handle_compression(output_dir, &args.compression)?;
+    if let Some(archive_path) = &archive_path {
         info!("Archive created: {}", archive_path);
     }
+    output_manager.report_output_size(archive_path.as_deref())?;
 
-    info!("Files saved to: {}", output_dir);
-    info!("Collection completed successfully");
+    // An interrupted collection keeps its directory so it can be continued with --resume. A
+    // streamed archive never had a directory
+    if args.archive_only
+        && !args.stream_archive
+        && let Some(archive_path) = &archive_path
+    {
+        if summary.interrupted {
+            warn!(
+                "Keeping {} despite --archive-only so the collection can be resumed",
+                output_dir
+            );
+        } else {
+            output_manager.remove_archived_output(output_dir, archive_path)?;
+        }
+    }
+
+    if !args.stream_archive {
+        info!("Files saved to: {}", output_dir);
+    }
+    // Repeat misconfigurations last so they are not lost in the collection log
+    for warning in &summary.warnings {
+        warn!("{}", warning);
+    }
+    if args.quiet {
+        println!("{}", archive_path.as_deref().unwrap_or(output_dir));
+    }
     Ok(())
 }
 
-fn init_logging(verbose: bool) {
+/// Credentials for connecting with --server instead of a kubeconfig
+fn token_auth(args: &Args) -> Result<k8s::TokenAuth> {
+    let server = args
+        .server
+        .clone()
+        .context("--kubeconfig or --server is required")?;
+
+    Ok(k8s::TokenAuth {
+        server,
+        token: args.token.clone(),
+        token_file: args.token_file.clone(),
+        ca_file: args.ca_file.clone(),
+        insecure_skip_tls_verify: args.insecure_skip_tls_verify,
+    })
+}
+
+/// Parse a duration such as "30", "30s", "5m" or "1h"
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit_secs) = match value.char_indices().last() {
+        Some((i, 's')) => (&value[..i], 1),
+        Some((i, 'm')) => (&value[..i], 60),
+        Some((i, 'h')) => (&value[..i], 3600),
+        _ => (value, 1),
+    };
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}', expected e.g. 30s, 5m or 1h", value))?;
+    let secs = number
+        .checked_mul(unit_secs)
+        .ok_or_else(|| format!("duration '{}' is too large", value))?;
+    Ok(Duration::from_secs(secs))
+}
+
+/// Parse an archive folder name: a single path component, so every entry stays inside it
+fn parse_tar_root_dir(value: &str) -> Result<String, String> {
+    let mut components = Path::new(value).components();
+    match (components.next(), components.next()) {
+        (Some(std::path::Component::Normal(name)), None)
+            if name == value && !value.contains(['/', '\\']) =>
+        {
+            Ok(value.to_string())
+        }
+        _ => Err(format!(
+            "invalid archive folder name '{}', expected a single name without '..' or separators",
+            value
+        )),
+    }
+}
+
+/// Parse a key=value label
+fn parse_label(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, label_value)) if !key.is_empty() => {
+            Ok((key.to_string(), label_value.to_string()))
+        }
+        _ => Err(format!("invalid label '{}', expected key=value", value)),
+    }
+}
+
+/// Parse an RFC 6901 JSON Pointer; the empty pointer (whole document) is rejected
+fn parse_json_pointer(value: &str) -> Result<String, String> {
+    if !value.starts_with('/') {
+        return Err(format!(
+            "invalid JSON Pointer '{}', expected a path starting with /",
+            value
+        ));
+    }
+    Ok(value.to_string())
+}
+
+/// Table of list access per resource type (rows) and namespace (columns): the collected
+/// namespaces, then the other (system) namespaces checked, and a final column for
+/// cluster-scoped types, for --preflight-only
+fn access_matrix(access: &[k8s::ListAccess], namespaces: &[String]) -> String {
+    const CLUSTER_COLUMN: &str = "(cluster)";
+    let mut resources: Vec<&str> = Vec::new();
+    let mut columns: Vec<&str> = namespaces.iter().map(String::as_str).collect();
+    for check in access {
+        if !resources.contains(&check.resource.as_str()) {
+            resources.push(&check.resource);
+        }
+        if let Some(namespace) = &check.namespace
+            && !columns.contains(&namespace.as_str())
+        {
+            columns.push(namespace);
+        }
+    }
+    columns.push(CLUSTER_COLUMN);
+    let first_width = resources.iter().map(|r| r.len()).max().unwrap_or(0).max(8);
+
+    let mut matrix = format!("{:<first_width$}", "RESOURCE");
+    for column in &columns {
+        matrix.push_str(&format!("  {}", column));
+    }
+    matrix.push('\n');
+    for resource in resources {
+        let mut row = format!("{:<first_width$}", resource);
+        for column in &columns {
+            let namespace = (*column != CLUSTER_COLUMN).then_some(*column);
+            let cell = access
+                .iter()
+                .find(|check| check.resource == resource && check.namespace.as_deref() == namespace)
+                .map_or("-", |check| if check.allowed { "yes" } else { "DENIED" });
+            row.push_str(&format!("  {:<width$}", cell, width = column.len()));
+        }
+        matrix.push_str(row.trim_end());
+        matrix.push('\n');
+    }
+    matrix
+}
+
+/// Parse a GROUP=VERSION API version preference, e.g. helm.cattle.io=v1
+fn parse_api_version_preference(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((group, version)) if !group.is_empty() && !version.is_empty() => {
+            Ok((group.to_string(), version.to_string()))
+        }
+        _ => Err(format!(
+            "invalid API version preference '{}', expected group=version",
+            value
+        )),
+    }
+}
+
+/// Parse an octal permission mode such as 0600 or 750
+fn parse_mode(value: &str) -> Result<u32, String> {
+    let digits = value.trim_start_matches("0o");
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .ok_or_else(|| format!("invalid octal mode '{}'", value))
+}
+
+/// Source named in --file-header banners: the kubeconfig context, falling back to the
+/// kubeconfig path, or the --server URL; None when banners are off
+fn header_source(args: &Args, kubeconfig: Option<&str>, context: Option<&str>) -> Option<String> {
+    if !args.file_header {
+        return None;
+    }
+
+    let source = match (kubeconfig, context) {
+        (_, Some(context)) => format!("context {}", context),
+        (Some(kubeconfig), None) => match kube::config::Kubeconfig::read_from(kubeconfig)
+            .ok()
+            .and_then(|config| config.current_context)
+        {
+            Some(context) => format!("context {}", context),
+            None => format!("kubeconfig {}", kubeconfig),
+        },
+        (None, None) => format!("server {}", args.server.as_deref().unwrap_or("unknown")),
+    };
+    Some(source)
+}
+
+/// File and directory modes to apply to the output, or None where permissions are unsupported
+fn output_modes(args: &Args) -> (Option<u32>, Option<u32>) {
+    if cfg!(unix) {
+        (
+            Some(args.file_mode.unwrap_or(DEFAULT_FILE_MODE)),
+            Some(args.dir_mode.unwrap_or(DEFAULT_DIR_MODE)),
+        )
+    } else {
+        (None, None)
+    }
+}
+
+/// Namespaced resources collected for saving, one list per resource type
+struct NamespacedResources {
+    pods: Vec<Value>,
+    services: Vec<Value>,
+    deployments: Vec<Value>,
+    configmaps: Vec<Value>,
+    secrets: Vec<Value>,
+    resourcequotas: Vec<Value>,
+    serviceaccounts: Vec<Value>,
+    ingresses: Vec<Value>,
+    helmcharts: Vec<Value>,
+    helmchartconfigs: Vec<Value>,
+}
+
+impl NamespacedResources {
+    /// Collect all resource types from the namespaces, running up to type_concurrency of them at
+    /// once
+    async fn collect(
+        kube_client: &k8s::KubeClient,
+        namespaces: &[String],
+        type_concurrency: usize,
+    ) -> Result<Self> {
+        let type_limit = Semaphore::new(type_concurrency);
+        let (
+            pods,
+            services,
+            deployments,
+            configmaps,
+            secrets,
+            resourcequotas,
+            serviceaccounts,
+            ingresses,
+            helmcharts,
+            helmchartconfigs,
+        ) = tokio::try_join!(
+            with_permit(&type_limit, kube_client.collect_pods(namespaces)),
+            with_permit(&type_limit, kube_client.collect_services(namespaces)),
+            with_permit(&type_limit, kube_client.collect_deployments(namespaces)),
+            with_permit(&type_limit, kube_client.collect_configmaps(namespaces)),
+            with_permit(&type_limit, kube_client.collect_secrets(namespaces)),
+            with_permit(&type_limit, kube_client.collect_resourcequotas(namespaces)),
+            with_permit(&type_limit, kube_client.collect_serviceaccounts(namespaces)),
+            with_permit(&type_limit, kube_client.collect_ingresses(namespaces)),
+            with_permit(&type_limit, kube_client.collect_helmcharts(namespaces)),
+            with_permit(
+                &type_limit,
+                kube_client.collect_helmchartconfigs(namespaces)
+            ),
+        )?;
+
+        Ok(Self {
+            pods,
+            services,
+            deployments,
+            configmaps,
+            secrets,
+            resourcequotas,
+            serviceaccounts,
+            ingresses,
+            helmcharts,
+            helmchartconfigs,
+        })
+    }
+
+    /// Add the resources a retry pass fetched for lists that failed in the first pass and
+    /// succeeded on retry
+    fn merge_recovered(
+        &mut self,
+        retry: Self,
+        failed_lists: &HashSet<(String, String)>,
+        still_failed: &HashSet<(String, String)>,
+    ) {
+        let merge = |resources: &mut Vec<Value>, retried: Vec<Value>, resource_type: &str| {
+            resources.extend(retried.into_iter().filter(|resource| {
+                let namespace = resource
+                    .get("metadata")
+                    .and_then(|m| m.get("namespace"))
+                    .and_then(|ns| ns.as_str())
+                    .unwrap_or_default();
+                let list = (namespace.to_string(), resource_type.to_string());
+                failed_lists.contains(&list) && !still_failed.contains(&list)
+            }));
+        };
+
+        merge(&mut self.pods, retry.pods, "pods");
+        merge(&mut self.services, retry.services, "services");
+        merge(&mut self.deployments, retry.deployments, "deployments");
+        merge(&mut self.configmaps, retry.configmaps, "configmaps");
+        merge(&mut self.secrets, retry.secrets, "secrets");
+        merge(
+            &mut self.resourcequotas,
+            retry.resourcequotas,
+            "resourcequotas",
+        );
+        merge(
+            &mut self.serviceaccounts,
+            retry.serviceaccounts,
+            "serviceaccounts",
+        );
+        merge(&mut self.ingresses, retry.ingresses, "ingresses");
+        merge(&mut self.helmcharts, retry.helmcharts, "helmcharts");
+        merge(
+            &mut self.helmchartconfigs,
+            retry.helmchartconfigs,
+            "helmchartconfigs",
+        );
+    }
+}
+
+/// Run a collection once a type-concurrency permit is available
+async fn with_permit<T>(
+    limit: &Semaphore,
+    collection: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let _permit = limit
+        .acquire()
+        .await
+        .context("Type concurrency limiter was closed")?;
+    collection.await
+}
+
+/// Start every --redact-path pointer at zero so unmatched pointers still show in the summary,
+/// and the Secret key counts when --secret-keep-keys is given
+fn redaction_counts(args: &Args) -> RedactionCounts {
+    RedactionCounts {
+        paths: args
+            .redact_path
+            .iter()
+            .map(|pointer| (pointer.clone(), 0))
+            .collect(),
+        secret_keys: args
+            .secret_keep_keys
+            .as_ref()
+            .map(|_| SecretKeyCounts::default()),
+    }
+}
+
+/// Apply the user's output options to a resource just before it is written, counting what the
+/// redaction options removed in `redactions`
+fn prepare_for_save(resource: &mut Value, args: &Args, redactions: &mut RedactionCounts) {
+    if resource.get("kind").and_then(|k| k.as_str()) == Some("ServiceAccount") {
+        drop_service_account_token_refs(resource);
+    }
+
+    if args.redact_tls_keys
+        && secret_type(resource) == Some(analysis::TLS_SECRET)
+        && let Some(key) = resource.pointer_mut("/data/tls.key")
+    {
+        *key = Value::String(String::new());
+    }
+
+    if let (Some(keep_keys), Some(counts)) = (&args.secret_keep_keys, &mut redactions.secret_keys)
+        && resource.get("kind").and_then(|k| k.as_str()) == Some("Secret")
+        && let Some(data) = resource.get_mut("data").and_then(|d| d.as_object_mut())
+    {
+        for (key, value) in data.iter_mut() {
+            if keep_keys.contains(key) {
+                counts.kept += 1;
+            } else {
+                *value = Value::String(String::new());
+                counts.redacted += 1;
+            }
+        }
+    }
+
+    for pointer in &args.redact_path {
+        if let Some(field) = resource.pointer_mut(pointer) {
+            *field = Value::String("REDACTED".to_string());
+            *redactions.paths.entry(pointer.clone()).or_default() += 1;
+        }
+    }
+
+    if args.prune_empty {
+        prune_empty(resource);
+    }
+
+    let Some(metadata) = resource.get_mut("metadata").and_then(|m| m.as_object_mut()) else {
+        return;
+    };
+
+    if args.strip_namespace {
+        metadata.remove("namespace");
+    }
+
+    if !args.strip_annotation_prefixes.is_empty()
+        && let Some(annotations) = metadata
+            .get_mut("annotations")
+            .and_then(|a| a.as_object_mut())
+    {
+        annotations.retain(|key, _| {
+            !args
+                .strip_annotation_prefixes
+                .iter()
+                .any(|prefix| !prefix.is_empty() && key.starts_with(prefix.as_str()))
+        });
+        if annotations.is_empty() {
+            metadata.remove("annotations");
+        }
+    }
+
+    if !args.inject_label.is_empty() {
+        let labels = metadata
+            .entry("labels")
+            .or_insert_with(|| Value::Object(Default::default()));
+        if let Some(labels) = labels.as_object_mut() {
+            for (key, value) in &args.inject_label {
+                labels.insert(key.clone(), Value::String(value.clone()));
+            }
+        }
+    }
+}
+
+/// Keys whose empty value means something (select everything, a scratch volume, a spec the
+/// schema requires), so --prune-empty keeps them
+const NEVER_PRUNED: &[&str] = &[
+    "spec",
+    "selector",
+    "podSelector",
+    "namespaceSelector",
+    "emptyDir",
+];
+
+/// Recursively remove null values and empty objects and arrays from object fields, innermost
+/// first. Array elements are never removed, as `ingress: [{}]` (allow all) differs from `[]`
+fn prune_empty(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for field in fields.values_mut() {
+                prune_empty(field);
+            }
+            fields.retain(|key, field| {
+                NEVER_PRUNED.contains(&key.as_str())
+                    || !match field {
+                        Value::Null => true,
+                        Value::Object(object) => object.is_empty(),
+                        Value::Array(array) => array.is_empty(),
+                        _ => false,
+                    }
+            });
+        }
+        Value::Array(items) => items.iter_mut().for_each(prune_empty),
+        _ => {}
+    }
+}
+
+/// Remove references to auto-generated `{name}-token-*` secrets from a ServiceAccount's
+/// `secrets` list. Those secrets are regenerated, imagePullSecrets are left untouched.
+fn drop_service_account_token_refs(service_account: &mut Value) {
+    let Some(name) = service_account
+        .get("metadata")
+        .and_then(|m| m.get("name"))
+        .and_then(|n| n.as_str())
+    else {
+        return;
+    };
+    let token_prefix = format!("{}-token-", name);
+
+    let Some(object) = service_account.as_object_mut() else {
+        return;
+    };
+    if let Some(Value::Array(secrets)) = object.get_mut("secrets") {
+        secrets.retain(|secret| {
+            !secret
+                .get("name")
+                .and_then(|n| n.as_str())
+                .is_some_and(|n| n.starts_with(&token_prefix))
+        });
+        if secrets.is_empty() {
+            object.remove("secrets");
+        }
+    }
+}
+
+/// Clone the resources that belong to the given namespace
+fn resources_in_namespace(resources: &[Value], namespace: &str) -> Vec<Value> {
+    resources
+        .iter()
+        .filter(|resource| {
+            resource
+                .get("metadata")
+                .and_then(|m| m.get("namespace"))
+                .and_then(|ns| ns.as_str())
+                == Some(namespace)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Whether a resource is managed by a controller through an ownerReference
+fn has_owner(resource: &Value) -> bool {
+    resource
+        .pointer("/metadata/ownerReferences")
+        .and_then(|o| o.as_array())
+        .is_some_and(|owners| !owners.is_empty())
+}
+
+/// Get the type of a Secret resource
+fn secret_type(secret: &Value) -> Option<&str> {
+    secret.get("type").and_then(|t| t.as_str())
+}
+
+/// Remove secrets of the given type, returning the dropped ones
+fn exclude_secrets_of_type(secrets: &mut Vec<Value>, excluded_type: &str) -> Vec<Value> {
+    secrets
+        .extract_if(.., |secret| secret_type(secret) == Some(excluded_type))
+        .collect()
+}
+
+fn init_logging(verbose: bool, quiet: bool) {
+    if quiet {
+        // Keep stdout clean for the archive path
+        tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::ERROR)
+            .with_writer(std::io::stderr)
+            .init();
+        return;
+    }
+
     let level = if verbose {
         tracing::Level::DEBUG
     } else {
@@ -243,3 +2045,116 @@ fn init_logging(verbose: bool) {
 
     tracing_subscriber::fmt().with_max_level(level).init();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn args(extra: &[&str]) -> Args {
+        let mut argv = vec!["ketchup", "--kubeconfig", "kubeconfig.yaml"];
+        argv.extend_from_slice(extra);
+        Args::parse_from(argv)
+    }
+
+    #[test]
+    fn service_account_token_refs_are_dropped_and_pull_secrets_kept() {
+        let mut service_account = json!({
+            "apiVersion": "v1",
+            "kind": "ServiceAccount",
+            "metadata": {"name": "default", "namespace": "apps"},
+            "secrets": [
+                {"name": "default-token-x7k2p"},
+                {"name": "default-token-9qzrm"},
+            ],
+            "imagePullSecrets": [
+                {"name": "registry-credentials"},
+                {"name": "default-token-lookalike"},
+            ],
+        });
+        let pull_secrets = service_account["imagePullSecrets"].clone();
+
+        let args = args(&[]);
+        prepare_for_save(&mut service_account, &args, &mut redaction_counts(&args));
+
+        assert!(service_account.get("secrets").is_none());
+        assert_eq!(service_account["imagePullSecrets"], pull_secrets);
+    }
+
+    #[test]
+    fn service_account_keeps_secret_refs_that_are_not_tokens() {
+        let mut service_account = json!({
+            "kind": "ServiceAccount",
+            "metadata": {"name": "builder"},
+            "secrets": [{"name": "builder-token-abcde"}, {"name": "git-ssh-key"}],
+        });
+
+        drop_service_account_token_refs(&mut service_account);
+
+        assert_eq!(service_account["secrets"], json!([{"name": "git-ssh-key"}]));
+    }
+
+    #[test]
+    fn excluded_secrets_are_returned() {
+        let mut secrets = vec![
+            json!({"metadata": {"name": "app-tls"}, "type": "kubernetes.io/tls"}),
+            json!({"metadata": {"name": "legacy-token"}, "type": SERVICE_ACCOUNT_TOKEN_SECRET}),
+        ];
+
+        let excluded = exclude_secrets_of_type(&mut secrets, SERVICE_ACCOUNT_TOKEN_SECRET);
+
+        assert_eq!(secrets.len(), 1);
+        assert_eq!(secrets[0]["metadata"]["name"], "app-tls");
+        assert_eq!(excluded.len(), 1);
+        assert_eq!(excluded[0]["metadata"]["name"], "legacy-token");
+    }
+
+    #[test]
+    fn access_matrix_adds_system_namespace_columns() {
+        let check = |namespace: Option<&str>, resource: &str, allowed: bool| k8s::ListAccess {
+            namespace: namespace.map(str::to_string),
+            resource: resource.to_string(),
+            allowed,
+            reason: None,
+        };
+        let access = [
+            check(Some("apps"), "pods", true),
+            check(Some("kube-system"), "daemonsets.apps", false),
+            check(None, "nodes", true),
+        ];
+
+        let matrix = access_matrix(&access, &["apps".to_string()]);
+
+        let lines: Vec<&str> = matrix.lines().collect();
+        assert_eq!(
+            lines[0].split_whitespace().collect::<Vec<_>>(),
+            ["RESOURCE", "apps", "kube-system", "(cluster)"]
+        );
+        assert_eq!(
+            lines[2].split_whitespace().collect::<Vec<_>>(),
+            ["daemonsets.apps", "-", "DENIED", "-"]
+        );
+    }
+
+    #[test]
+    fn tar_root_dir_must_be_a_single_name() {
+        assert_eq!(
+            parse_tar_root_dir("support-bundle").as_deref(),
+            Ok("support-bundle")
+        );
+        for invalid in ["", ".", "..", "../x", "/abs", "a/b", "a\\b", "x/"] {
+            assert!(
+                parse_tar_root_dir(invalid).is_err(),
+                "{:?} accepted",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn parse_duration_rejects_overflow() {
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert!(parse_duration("9999999999999999h").is_err());
+    }
+}
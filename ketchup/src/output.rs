@@ -1,11 +1,201 @@
+use crate::analysis::{CniInfo, GpuInfo, IngressInfo, NodeInfo, ResourceUsage};
+use crate::state::{CollectionState, STATE_FILE};
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use tracing::info;
 
+/// Output directory holding cluster-scoped resources, in place of a namespace. A namespace may
+/// also be named cluster-scoped; this does not collide because only --crds-only saves
+/// cluster-scoped resources and it saves no namespaced ones. Flattened files use the `cluster`
+/// prefix instead
+pub const CLUSTER_SCOPED_DIR: &str = "cluster-scoped";
+
+/// Suffix of a streamed archive until it is complete, so a failed or interrupted collection
+/// never leaves a truncated archive under the final name
+const PARTIAL_ARCHIVE_SUFFIX: &str = ".partial";
+
+/// File name prefix of flattened cluster-scoped resources, in place of the namespace
+const FLATTENED_CLUSTER_PREFIX: &str = "cluster";
+
+/// Number of resources saved, per resource type
+#[derive(Debug, Clone, Default)]
+pub struct ResourceCounts {
+    pub pods: usize,
+    pub services: usize,
+    pub deployments: usize,
+    pub configmaps: usize,
+    pub secrets: usize,
+    pub resourcequotas: usize,
+    pub serviceaccounts: usize,
+    pub ingresses: usize,
+    pub helmcharts: usize,
+    pub helmchartconfigs: usize,
+}
+
+impl ResourceCounts {
+    /// Counts as (resource type, display name, count), in output order
+    pub fn by_type(&self) -> [(&'static str, &'static str, usize); 10] {
+        [
+            ("pods", "Pods", self.pods),
+            ("services", "Services", self.services),
+            ("deployments", "Deployments", self.deployments),
+            ("configmaps", "ConfigMaps", self.configmaps),
+            ("secrets", "Secrets", self.secrets),
+            ("resourcequotas", "ResourceQuotas", self.resourcequotas),
+            ("serviceaccounts", "ServiceAccounts", self.serviceaccounts),
+            ("ingresses", "Ingresses", self.ingresses),
+            ("helmcharts", "HelmCharts", self.helmcharts),
+            (
+                "helmchartconfigs",
+                "HelmChartConfigs",
+                self.helmchartconfigs,
+            ),
+        ]
+    }
+
+    pub fn total(&self) -> usize {
+        self.by_type().iter().map(|(_, _, count)| count).sum()
+    }
+
+    pub fn add(&mut self, other: &ResourceCounts) {
+        self.pods += other.pods;
+        self.services += other.services;
+        self.deployments += other.deployments;
+        self.configmaps += other.configmaps;
+        self.secrets += other.secrets;
+        self.resourcequotas += other.resourcequotas;
+        self.serviceaccounts += other.serviceaccounts;
+        self.ingresses += other.ingresses;
+        self.helmcharts += other.helmcharts;
+        self.helmchartconfigs += other.helmchartconfigs;
+    }
+}
+
+/// Resources saved for a single namespace
+#[derive(Debug, Clone)]
+pub struct NamespaceStats {
+    pub namespace: String,
+    pub counts: ResourceCounts,
+}
+
+/// Resources that were collected but deliberately not saved
+#[derive(Debug, Clone)]
+pub struct SkippedResources {
+    pub kind: String,
+    pub reason: String,
+    pub count: usize,
+}
+
+/// Collection overview shared by the YAML summary and the Markdown report
+#[derive(Debug, Clone)]
+pub struct ClusterSummary {
+    pub kubernetes_version: String,
+    /// UID of the kube-system Namespace, or "unknown" when it could not be read
+    pub cluster_id: String,
+    pub namespaces: Vec<NamespaceStats>,
+    pub totals: ResourceCounts,
+    pub nodes: Vec<NodeInfo>,
+    pub cni: Option<CniInfo>,
+    pub ingress: Option<IngressInfo>,
+    pub gpu: Option<GpuInfo>,
+    /// Rancher role from the management section of suse-edge-analysis.yaml
+    pub rancher_role: Option<String>,
+    /// Scheduled Rancher Backups, None when the Rancher Backup Operator's CRD is missing
+    pub scheduled_backups: Option<usize>,
+    pub resource_usage: Option<ResourceUsage>,
+    /// Resources left out because they match the --only-changed-from baseline
+    pub unchanged: Option<usize>,
+    pub skipped: Vec<SkippedResources>,
+    pub notes: Vec<String>,
+    /// Misconfigurations the operator must see, repeated at the end of the run
+    pub warnings: Vec<String>,
+    pub interrupted: bool,
+    /// CustomResourceDefinitions saved by a --crds-only collection, which saves nothing else
+    pub crd_schemas: Option<usize>,
+    pub redactions: RedactionCounts,
+}
+
+/// What the redaction options removed from saved resources
+#[derive(Debug, Clone, Default)]
+pub struct RedactionCounts {
+    /// Values replaced per --redact-path JSON Pointer
+    pub paths: BTreeMap<String, usize>,
+    /// Secret data keys kept and blanked under --secret-keep-keys, None without it
+    pub secret_keys: Option<SecretKeyCounts>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SecretKeyCounts {
+    pub kept: usize,
+    pub redacted: usize,
+}
+
+impl ClusterSummary {
+    pub fn new(kubernetes_version: String, namespaces: Vec<NamespaceStats>) -> Self {
+        let mut totals = ResourceCounts::default();
+        for stats in &namespaces {
+            totals.add(&stats.counts);
+        }
+
+        Self {
+            kubernetes_version,
+            cluster_id: "unknown".to_string(),
+            namespaces,
+            totals,
+            nodes: Vec::new(),
+            cni: None,
+            ingress: None,
+            gpu: None,
+            rancher_role: None,
+            scheduled_backups: None,
+            resource_usage: None,
+            unchanged: None,
+            skipped: Vec::new(),
+            notes: Vec::new(),
+            warnings: Vec::new(),
+            interrupted: false,
+            crd_schemas: None,
+            redactions: RedactionCounts::default(),
+        }
+    }
+}
+
+/// Outcome of collecting one cluster from a fleet file
+#[derive(Debug, Clone)]
+pub struct FleetClusterResult {
+    pub name: String,
+    pub context: Option<String>,
+    pub output_dir: Option<String>,
+    pub summary: Option<ClusterSummary>,
+    pub error: Option<String>,
+    pub duration: Duration,
+}
+
+/// Gzipped tar archive that output files are streamed into
+type ArchiveBuilder = tar::Builder<flate2::write::GzEncoder<fs::File>>;
+
 pub struct OutputManager {
     base_dir: String,
+    cluster_name: Option<String>,
+    flatten: bool,
+    by_kind: bool,
+    resume_dir: Option<String>,
+    file_mode: Option<u32>,
+    dir_mode: Option<u32>,
+    file_header: Option<String>,
+    stream_archive: bool,
+    tar_root_dir: Option<String>,
+    parallel_writes: bool,
+    /// Open archive while streaming, until handle_compression finishes it
+    archive: Mutex<Option<ArchiveBuilder>>,
+    bytes_written: AtomicU64,
     timestamp: DateTime<Utc>,
 }
 
@@ -13,344 +203,755 @@ impl OutputManager {
     pub fn new_output_manager(base_dir: String) -> Self {
         Self {
             base_dir,
+            cluster_name: None,
+            flatten: false,
+            by_kind: false,
+            resume_dir: None,
+            file_mode: None,
+            dir_mode: None,
+            file_header: None,
+            stream_archive: false,
+            tar_root_dir: None,
+            parallel_writes: false,
+            archive: Mutex::new(None),
+            bytes_written: AtomicU64::new(0),
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// Output manager for one cluster of a fleet, writing to ketchup-{name}-{timestamp}
+    pub fn new_cluster_output_manager(base_dir: String, cluster_name: &str) -> Self {
+        Self {
+            base_dir,
+            cluster_name: Some(cluster_name.to_string()),
+            flatten: false,
+            by_kind: false,
+            resume_dir: None,
+            file_mode: None,
+            dir_mode: None,
+            file_header: None,
+            stream_archive: false,
+            tar_root_dir: None,
+            parallel_writes: false,
+            archive: Mutex::new(None),
+            bytes_written: AtomicU64::new(0),
             timestamp: Utc::now(),
         }
     }
 
+    /// Write all manifests into a single manifests/ directory instead of the namespace tree
+    pub fn with_flatten(mut self, flatten: bool) -> Self {
+        self.flatten = flatten;
+        self
+    }
+
+    /// Group manifests by kind across namespaces under by-kind/ instead of the namespace tree
+    pub fn with_by_kind_layout(mut self, by_kind: bool) -> Self {
+        self.by_kind = by_kind;
+        self
+    }
+
+    /// Manifest layout and a matching kubectl apply command, for the collection summary
+    fn layout_description(&self, output_dir: &str) -> Value {
+        let (layout, manifests, apply) = if self.flatten {
+            (
+                "flattened",
+                "manifests/{namespace}__{kind}__{name}",
+                format!("kubectl apply -f {}/manifests/", output_dir),
+            )
+        } else if self.by_kind {
+            (
+                "by-kind",
+                "by-kind/{kind}/{namespace}__{name}, by-kind/{kind}/{name} when cluster-scoped",
+                format!("kubectl apply -R -f {}/by-kind/", output_dir),
+            )
+        } else {
+            (
+                "by-namespace",
+                "{namespace}/{resource_type}/{name}",
+                format!("kubectl apply -R -f {}/<namespace>/", output_dir),
+            )
+        };
+        serde_json::json!({
+            "layout": layout,
+            "manifests": manifests,
+            "apply_hint": apply
+        })
+    }
+
+    /// Continue an earlier collection in its existing output directory instead of a new one
+    pub fn with_resume_dir(mut self, resume_dir: Option<String>) -> Self {
+        self.resume_dir = resume_dir;
+        self
+    }
+
+    /// Permission bits applied to every written file and created directory (Unix only)
+    pub fn with_permissions(mut self, file_mode: Option<u32>, dir_mode: Option<u32>) -> Self {
+        self.file_mode = file_mode;
+        self.dir_mode = dir_mode;
+        self
+    }
+
+    /// Prefix saved YAML manifests with a comment banner naming this source cluster
+    pub fn with_file_header(mut self, source: Option<String>) -> Self {
+        self.file_header = source;
+        self
+    }
+
+    /// Write every output file straight into the archive instead of an output directory that
+    /// is archived afterwards, so the collection never needs disk space for both
+    pub fn with_stream_archive(mut self, stream_archive: bool) -> Self {
+        self.stream_archive = stream_archive;
+        self
+    }
+
+    /// Name of the archive's top-level folder, instead of the output directory's name
+    pub fn with_tar_root_dir(mut self, tar_root_dir: Option<String>) -> Self {
+        self.tar_root_dir = tar_root_dir;
+        self
+    }
+
+    /// Serialize and write the files of each namespace and resource type batch on all CPUs
+    pub fn with_parallel_writes(mut self, parallel_writes: bool) -> Self {
+        self.parallel_writes = parallel_writes;
+        self
+    }
+
+    /// Top-level folder of the archive, so it extracts into a single directory
+    fn archive_root(&self, output_dir: &str) -> String {
+        self.tar_root_dir.clone().unwrap_or_else(|| {
+            Path::new(output_dir)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "ketchup".to_string())
+        })
+    }
+
+    /// Total bytes written to output files so far
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+
+    /// Path of the timestamped output directory
+    pub fn output_dir_path(&self) -> String {
+        if let Some(resume_dir) = &self.resume_dir {
+            return resume_dir.clone();
+        }
+
+        let timestamp_str = self.timestamp.format("%Y-%m-%d-%H-%M-%S");
+        match &self.cluster_name {
+            Some(name) => format!("{}/ketchup-{}-{}", self.base_dir, name, timestamp_str),
+            None => format!("{}/ketchup-{}", self.base_dir, timestamp_str),
+        }
+    }
+
     /// Create timestamped output directory
     pub fn create_output_directory(&self) -> Result<String> {
-        let timestamp_str = self.timestamp.format("%Y-%m-%d-%H-%M-%S");
-        let output_dir = format!("{}/ketchup-{}", self.base_dir, timestamp_str);
+        let output_dir = self.output_dir_path();
+
+        if self.stream_archive {
+            let partial_name = format!("{}.tar.gz{}", output_dir, PARTIAL_ARCHIVE_SUFFIX);
+            info!("Streaming output into archive: {}", partial_name);
+            self.create_dir(&self.base_dir)
+                .context("Failed to create output directory")?;
+            let tar_gz =
+                fs::File::create(&partial_name).context("Failed to create archive file")?;
+            let enc = flate2::write::GzEncoder::new(tar_gz, flate2::Compression::default());
+            *self.archive.lock().unwrap_or_else(|e| e.into_inner()) = Some(tar::Builder::new(enc));
+            return Ok(output_dir);
+        }
 
         info!("Creating output directory: {}", output_dir);
-        fs::create_dir_all(&output_dir).context("Failed to create output directory")?;
+        self.create_dir(&output_dir)
+            .context("Failed to create output directory")?;
 
         Ok(output_dir)
     }
 
-    /// Save individual pods to namespace/pods/ structure
-    pub fn save_pods_individually(
+    /// Save individual resources to namespace/{resource_type}/ structure, to
+    /// manifests/{namespace}__{kind}__{name} (manifests/cluster__{kind}__{name} when
+    /// cluster-scoped) when flattening, or to
+    /// by-kind/{kind}/{namespace}__{name} (by-kind/{kind}/{name} when cluster-scoped) for the
+    /// by-kind layout
+    pub fn save_resources_individually(
         &self,
         output_dir: &str,
         namespace: &str,
-        pods: &[Value],
+        resource_type: &str,
+        resources: &[Value],
         format: &str,
     ) -> Result<usize> {
-        let pods_dir = format!("{}/{}/pods", output_dir, namespace);
-        fs::create_dir_all(&pods_dir).context("Failed to create namespace pods directory")?;
-
-        let mut saved_count = 0;
-        for pod in pods {
-            if let Some(pod_name) = pod
-                .get("metadata")
-                .and_then(|m| m.get("name"))
-                .and_then(|n| n.as_str())
-            {
-                match format {
-                    "json" => {
-                        let filename = format!("{}/{}.json", pods_dir, pod_name);
-                        let content = serde_json::to_string_pretty(pod)?;
-                        fs::write(&filename, content)?;
-                        saved_count += 1;
-                    }
-                    "yaml" => {
-                        let filename = format!("{}/{}.yaml", pods_dir, pod_name);
-                        let content = serde_yaml::to_string(pod)?;
-                        fs::write(&filename, content)?;
-                        saved_count += 1;
-                    }
-                    "both" => {
-                        let json_file = format!("{}/{}.json", pods_dir, pod_name);
-                        let yaml_file = format!("{}/{}.yaml", pods_dir, pod_name);
+        // Without resources there is no kind to name a by-kind directory after
+        if self.by_kind && resources.is_empty() {
+            return Ok(0);
+        }
+        // A batch holds one resource type, so one kind
+        let kind = resources
+            .iter()
+            .find_map(|resource| resource.get("kind").and_then(|k| k.as_str()))
+            .unwrap_or(resource_type);
+        let resource_dir = if self.flatten {
+            format!("{}/manifests", output_dir)
+        } else if self.by_kind {
+            format!("{}/by-kind/{}", output_dir, kind)
+        } else {
+            format!("{}/{}/{}", output_dir, namespace, resource_type)
+        };
+        self.create_dir(&resource_dir)
+            .with_context(|| format!("Failed to create {} directory", resource_dir))?;
 
-                        let json_content = serde_json::to_string_pretty(pod)?;
-                        let yaml_content = serde_yaml::to_string(pod)?;
+        let named: Vec<(String, &Value)> = resources
+            .iter()
+            .filter_map(|resource| {
+                let resource_name = resource
+                    .get("metadata")
+                    .and_then(|m| m.get("name"))
+                    .and_then(|n| n.as_str())?;
+                let file_stem = if self.flatten {
+                    let kind = resource
+                        .get("kind")
+                        .and_then(|k| k.as_str())
+                        .unwrap_or(resource_type);
+                    let prefix = if namespace == CLUSTER_SCOPED_DIR {
+                        FLATTENED_CLUSTER_PREFIX
+                    } else {
+                        namespace
+                    };
+                    format!("{}__{}__{}", prefix, kind, resource_name)
+                } else if self.by_kind && namespace != CLUSTER_SCOPED_DIR {
+                    format!("{}__{}", namespace, resource_name)
+                } else {
+                    resource_name.to_string()
+                };
+                Some((file_stem, resource))
+            })
+            .collect();
 
-                        fs::write(&json_file, json_content)?;
-                        fs::write(&yaml_file, yaml_content)?;
-                        saved_count += 1;
-                    }
-                    _ => return Err(anyhow::anyhow!("Invalid format: {}", format)),
-                }
-            }
+        let write_all = |batch: &[(String, &Value)]| {
+            batch.iter().try_for_each(|(file_stem, resource)| {
+                self.write_resource(&resource_dir, file_stem, resource, format)
+            })
+        };
+        let threads = if self.parallel_writes {
+            std::thread::available_parallelism().map_or(1, |n| n.get())
+        } else {
+            1
+        };
+        if threads > 1 && named.len() > 1 {
+            std::thread::scope(|scope| {
+                let workers: Vec<_> = named
+                    .chunks(named.len().div_ceil(threads))
+                    .map(|batch| scope.spawn(move || write_all(batch)))
+                    .collect();
+                workers.into_iter().try_for_each(|worker| {
+                    worker
+                        .join()
+                        .map_err(|_| anyhow::anyhow!("File writer thread panicked"))?
+                })
+            })?;
+        } else {
+            write_all(&named)?;
         }
+        let saved_count = named.len();
 
-        info!("Saved {} pods to {}", saved_count, pods_dir);
+        info!(
+            "Saved {} {} from namespace {} to {}",
+            saved_count, resource_type, namespace, resource_dir
+        );
         Ok(saved_count)
     }
 
-    /// Save individual services to namespace/services/ structure
-    pub fn save_services_individually(
+    /// Copy key kube-system ConfigMaps to cluster-config/{name} so the bootstrap configuration
+    /// is easy to find when planning a restore
+    pub fn save_cluster_config(
         &self,
         output_dir: &str,
-        namespace: &str,
-        services: &[Value],
+        configmaps: &[Value],
         format: &str,
     ) -> Result<usize> {
-        let services_dir = format!("{}/{}/services", output_dir, namespace);
-        fs::create_dir_all(&services_dir)
-            .context("Failed to create namespace services directory")?;
+        let config_dir = format!("{}/cluster-config", output_dir);
+        self.create_dir(&config_dir)
+            .with_context(|| format!("Failed to create {} directory", config_dir))?;
 
         let mut saved_count = 0;
-        for service in services {
-            if let Some(service_name) = service
+        for configmap in configmaps {
+            if let Some(name) = configmap
                 .get("metadata")
                 .and_then(|m| m.get("name"))
                 .and_then(|n| n.as_str())
             {
-                match format {
-                    "json" => {
-                        let filename = format!("{}/{}.json", services_dir, service_name);
-                        let content = serde_json::to_string_pretty(service)?;
-                        fs::write(&filename, content)?;
-                        saved_count += 1;
-                    }
-                    "yaml" => {
-                        let filename = format!("{}/{}.yaml", services_dir, service_name);
-                        let content = serde_yaml::to_string(service)?;
-                        fs::write(&filename, content)?;
-                        saved_count += 1;
-                    }
-                    "both" => {
-                        let json_file = format!("{}/{}.json", services_dir, service_name);
-                        let yaml_file = format!("{}/{}.yaml", services_dir, service_name);
-
-                        let json_content = serde_json::to_string_pretty(service)?;
-                        let yaml_content = serde_yaml::to_string(service)?;
-
-                        fs::write(&json_file, json_content)?;
-                        fs::write(&yaml_file, yaml_content)?;
-                        saved_count += 1;
-                    }
-                    _ => return Err(anyhow::anyhow!("Invalid format: {}", format)),
-                }
+                self.write_resource(&config_dir, name, configmap, format)?;
+                saved_count += 1;
             }
         }
 
-        info!("Saved {} services to {}", saved_count, services_dir);
+        info!(
+            "Copied {} bootstrap ConfigMaps to {}",
+            saved_count, config_dir
+        );
         Ok(saved_count)
     }
 
-    /// Save individual deployments to namespace/deployments/ structure
-    pub fn save_deployments_individually(
+    /// Write one resource as {file_stem}.json, .yaml and/or .ndjson in a directory
+    fn write_resource(
         &self,
-        output_dir: &str,
-        namespace: &str,
-        deployments: &[Value],
+        dir: &str,
+        file_stem: &str,
+        resource: &Value,
         format: &str,
-    ) -> Result<usize> {
-        let deployments_dir = format!("{}/{}/deployments", output_dir, namespace);
-        fs::create_dir_all(&deployments_dir)
-            .context("Failed to create namespace deployments directory")?;
-
-        let mut saved_count = 0;
-        for deployment in deployments {
-            if let Some(deployment_name) = deployment
-                .get("metadata")
-                .and_then(|m| m.get("name"))
-                .and_then(|n| n.as_str())
-            {
-                match format {
-                    "json" => {
-                        let filename = format!("{}/{}.json", deployments_dir, deployment_name);
-                        let content = serde_json::to_string_pretty(deployment)?;
-                        fs::write(&filename, content)?;
-                        saved_count += 1;
-                    }
-                    "yaml" => {
-                        let filename = format!("{}/{}.yaml", deployments_dir, deployment_name);
-                        let content = serde_yaml::to_string(deployment)?;
-                        fs::write(&filename, content)?;
-                        saved_count += 1;
-                    }
-                    "both" => {
-                        let json_file = format!("{}/{}.json", deployments_dir, deployment_name);
-                        let yaml_file = format!("{}/{}.yaml", deployments_dir, deployment_name);
-
-                        let json_content = serde_json::to_string_pretty(deployment)?;
-                        let yaml_content = serde_yaml::to_string(deployment)?;
+    ) -> Result<()> {
+        let (json, yaml, ndjson) = output_formats(format)?;
+        let path = format!("{}/{}", dir, file_stem);
 
-                        fs::write(&json_file, json_content)?;
-                        fs::write(&yaml_file, yaml_content)?;
-                        saved_count += 1;
-                    }
-                    _ => return Err(anyhow::anyhow!("Invalid format: {}", format)),
-                }
-            }
+        if json {
+            self.write_file(
+                &format!("{}.json", path),
+                serde_json::to_string_pretty(resource)?,
+            )?;
         }
-
-        info!("Saved {} deployments to {}", saved_count, deployments_dir);
-        Ok(saved_count)
+        if yaml {
+            self.write_file(
+                &format!("{}.yaml", path),
+                self.yaml_header() + &serde_yaml::to_string(resource)?,
+            )?;
+        }
+        if ndjson {
+            self.write_file(
+                &format!("{}.ndjson", path),
+                serde_json::to_string(resource)? + "\n",
+            )?;
+        }
+        Ok(())
     }
 
-    /// Save individual configmaps to namespace/configmaps/ structure
-    pub fn save_configmaps_individually(
+    /// Write resources as a single v1 List object at the output root
+    pub fn save_resource_list(
         &self,
         output_dir: &str,
-        namespace: &str,
-        configmaps: &[Value],
+        file_stem: &str,
+        items: Vec<Value>,
         format: &str,
-    ) -> Result<usize> {
-        let configmaps_dir = format!("{}/{}/configmaps", output_dir, namespace);
-        fs::create_dir_all(&configmaps_dir)
-            .context("Failed to create namespace configmaps directory")?;
+    ) -> Result<()> {
+        let (json, yaml, ndjson) = output_formats(format)?;
+        let item_count = items.len();
+        let path = format!("{}/{}", output_dir, file_stem);
 
-        let mut saved_count = 0;
-        for configmap in configmaps {
-            if let Some(configmap_name) = configmap
-                .get("metadata")
-                .and_then(|m| m.get("name"))
-                .and_then(|n| n.as_str())
-            {
-                match format {
-                    "json" => {
-                        let filename = format!("{}/{}.json", configmaps_dir, configmap_name);
-                        let content = serde_json::to_string_pretty(configmap)?;
-                        fs::write(&filename, content)?;
-                        saved_count += 1;
-                    }
-                    "yaml" => {
-                        let filename = format!("{}/{}.yaml", configmaps_dir, configmap_name);
-                        let content = serde_yaml::to_string(configmap)?;
-                        fs::write(&filename, content)?;
-                        saved_count += 1;
-                    }
-                    "both" => {
-                        let json_file = format!("{}/{}.json", configmaps_dir, configmap_name);
-                        let yaml_file = format!("{}/{}.yaml", configmaps_dir, configmap_name);
+        // NDJSON holds one item per line rather than the List wrapper
+        if ndjson {
+            let mut lines = String::new();
+            for item in &items {
+                lines.push_str(&serde_json::to_string(item)?);
+                lines.push('\n');
+            }
+            self.write_file(&format!("{}.ndjson", path), lines)
+                .with_context(|| format!("Failed to write {}.ndjson", file_stem))?;
+        }
 
-                        let json_content = serde_json::to_string_pretty(configmap)?;
-                        let yaml_content = serde_yaml::to_string(configmap)?;
+        let list = serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "List",
+            "metadata": { "resourceVersion": "" },
+            "items": items
+        });
 
-                        fs::write(&json_file, json_content)?;
-                        fs::write(&yaml_file, yaml_content)?;
-                        saved_count += 1;
-                    }
-                    _ => return Err(anyhow::anyhow!("Invalid format: {}", format)),
-                }
-            }
+        if json {
+            self.write_file(
+                &format!("{}.json", path),
+                serde_json::to_string_pretty(&list)?,
+            )
+            .with_context(|| format!("Failed to write {}.json", file_stem))?;
+        }
+        if yaml {
+            self.write_file(&format!("{}.yaml", path), serde_yaml::to_string(&list)?)
+                .with_context(|| format!("Failed to write {}.yaml", file_stem))?;
         }
 
-        info!("Saved {} configmaps to {}", saved_count, configmaps_dir);
-        Ok(saved_count)
+        info!("Saved {} resources as a List to {}", item_count, path);
+        Ok(())
+    }
+
+    /// Record saving progress so an interrupted or failed collection can be resumed
+    pub fn save_collection_state(&self, output_dir: &str, state: &CollectionState) -> Result<()> {
+        // The state lets --resume continue in the output directory, a streamed archive has none
+        if self.stream_archive {
+            return Ok(());
+        }
+        let content = serde_json::to_string_pretty(state)?;
+        self.write_file(&format!("{}/{}", output_dir, STATE_FILE), content)
+            .context("Failed to write collection state")
     }
 
-    /// Save individual secrets to namespace/secrets/ structure
-    pub fn save_secrets_individually(
+    /// Write an analysis report as YAML at the output root
+    pub fn write_yaml_report(
         &self,
         output_dir: &str,
-        namespace: &str,
-        secrets: &[Value],
-        format: &str,
-    ) -> Result<usize> {
-        let secrets_dir = format!("{}/{}/secrets", output_dir, namespace);
-        fs::create_dir_all(&secrets_dir).context("Failed to create namespace secrets directory")?;
-
-        let mut saved_count = 0;
-        for secret in secrets {
-            if let Some(secret_name) = secret
-                .get("metadata")
-                .and_then(|m| m.get("name"))
-                .and_then(|n| n.as_str())
-            {
-                match format {
-                    "json" => {
-                        let filename = format!("{}/{}.json", secrets_dir, secret_name);
-                        let content = serde_json::to_string_pretty(secret)?;
-                        fs::write(&filename, content)?;
-                        saved_count += 1;
-                    }
-                    "yaml" => {
-                        let filename = format!("{}/{}.yaml", secrets_dir, secret_name);
-                        let content = serde_yaml::to_string(secret)?;
-                        fs::write(&filename, content)?;
-                        saved_count += 1;
-                    }
-                    "both" => {
-                        let json_file = format!("{}/{}.json", secrets_dir, secret_name);
-                        let yaml_file = format!("{}/{}.yaml", secrets_dir, secret_name);
+        file_name: &str,
+        report: &Value,
+    ) -> Result<()> {
+        let filename = format!("{}/{}", output_dir, file_name);
+        info!("Creating report: {}", filename);
+        if let Some(parent) = Path::new(&filename).parent() {
+            self.create_dir(&parent.to_string_lossy())?;
+        }
 
-                        let json_content = serde_json::to_string_pretty(secret)?;
-                        let yaml_content = serde_yaml::to_string(secret)?;
+        let content = serde_yaml::to_string(report)
+            .with_context(|| format!("Failed to serialize {} to YAML", file_name))?;
+        self.write_file(&filename, content)
+            .with_context(|| format!("Failed to write {}", file_name))?;
 
-                        fs::write(&json_file, json_content)?;
-                        fs::write(&yaml_file, yaml_content)?;
-                        saved_count += 1;
-                    }
-                    _ => return Err(anyhow::anyhow!("Invalid format: {}", format)),
-                }
-            }
-        }
+        Ok(())
+    }
 
-        info!("Saved {} secrets to {}", saved_count, secrets_dir);
-        Ok(saved_count)
+    /// Write a plain-text report, such as a Graphviz graph, at the output root
+    pub fn write_text_report(
+        &self,
+        output_dir: &str,
+        file_name: &str,
+        content: &str,
+    ) -> Result<()> {
+        let filename = format!("{}/{}", output_dir, file_name);
+        info!("Creating report: {}", filename);
+        self.write_file(&filename, content)
+            .with_context(|| format!("Failed to write {}", file_name))
     }
 
     /// Create enhanced summary with per-namespace resource breakdown
     pub fn create_enhanced_summary(
         &self,
         output_dir: &str,
-        namespace_stats: &[(String, usize, usize, usize, usize, usize)], // Updated to include secrets
+        summary: &ClusterSummary,
     ) -> Result<()> {
-        let mut total_pods = 0;
-        let mut total_services = 0;
-        let mut total_deployments = 0;
-        let mut total_configmaps = 0;
-        let mut total_secrets = 0;
         let mut namespace_details = serde_json::Map::new();
 
-        for (
-            namespace,
-            pod_count,
-            service_count,
-            deployment_count,
-            configmap_count,
-            secret_count,
-        ) in namespace_stats
-        {
-            total_pods += pod_count;
-            total_services += service_count;
-            total_deployments += deployment_count;
-            total_configmaps += configmap_count;
-            total_secrets += secret_count;
-
-            namespace_details.insert(
-                namespace.clone(),
+        for stats in &summary.namespaces {
+            let mut details = serde_json::Map::new();
+            for (resource_type, _, count) in stats.counts.by_type() {
+                details.insert(format!("{}_collected", resource_type), count.into());
+            }
+            details.insert("total_resources".to_string(), stats.counts.total().into());
+            namespace_details.insert(stats.namespace.clone(), Value::Object(details));
+        }
+
+        let skipped_resources: Vec<Value> = summary
+            .skipped
+            .iter()
+            .map(|skipped| {
                 serde_json::json!({
-                    "pods_collected": pod_count,
-                    "services_collected": service_count,
-                    "deployments_collected": deployment_count,
-                    "configmaps_collected": configmap_count,
-                    "secrets_collected": secret_count,
-                    "total_resources": pod_count + service_count + deployment_count + configmap_count + secret_count
-                }),
+                    "kind": skipped.kind,
+                    "reason": skipped.reason,
+                    "count": skipped.count
+                })
+            })
+            .collect();
+
+        let mut cluster_summary = serde_json::Map::new();
+        cluster_summary.insert(
+            "kubernetes_version".to_string(),
+            summary.kubernetes_version.clone().into(),
+        );
+        cluster_summary.insert("total_nodes".to_string(), summary.nodes.len().into());
+        cluster_summary.insert("cni".to_string(), serde_json::json!(summary.cni));
+        if let Some(ingress) = &summary.ingress {
+            cluster_summary.insert("ingress".to_string(), serde_json::json!(ingress));
+        }
+        if let Some(gpu) = &summary.gpu {
+            cluster_summary.insert("gpu".to_string(), serde_json::json!(gpu));
+        }
+        if let Some(usage) = &summary.resource_usage {
+            cluster_summary.insert("resource_usage".to_string(), serde_json::json!(usage));
+        }
+        if let Some(unchanged) = summary.unchanged {
+            cluster_summary.insert("unchanged_from_baseline".to_string(), unchanged.into());
+        }
+        cluster_summary.insert(
+            "total_namespaces".to_string(),
+            summary.namespaces.len().into(),
+        );
+        for (resource_type, _, count) in summary.totals.by_type() {
+            cluster_summary.insert(format!("total_{}", resource_type), count.into());
+        }
+        cluster_summary.insert("total_resources".to_string(), summary.totals.total().into());
+        if let Some(crd_schemas) = summary.crd_schemas {
+            cluster_summary.insert(
+                "total_customresourcedefinitions".to_string(),
+                crd_schemas.into(),
             );
         }
 
-        let summary = serde_json::json!({
+        let redactions = &summary.redactions;
+        let layout = self.layout_description(output_dir);
+        let mut summary = serde_json::json!({
             "collection_info": {
                 "timestamp": self.timestamp.to_rfc3339(),
                 "tool": "ketchup",
-                "version": env!("CARGO_PKG_VERSION")
+                "version": env!("CARGO_PKG_VERSION"),
+                "cluster_id": summary.cluster_id,
+                "status": if summary.interrupted { "interrupted" } else { "complete" },
+                "scope": if summary.crd_schemas.is_some() { "crd_schemas_only" } else { "namespaced_resources" }
             },
-            "cluster_summary": {
-                "total_namespaces": namespace_stats.len(),
-                "total_pods": total_pods,
-                "total_services": total_services,
-                "total_deployments": total_deployments,
-                "total_configmaps": total_configmaps,
-                "total_secrets": total_secrets,
-                "total_resources": total_pods + total_services + total_deployments + total_configmaps + total_secrets
+            "cluster_summary": cluster_summary,
+            "namespace_details": namespace_details,
+            "output_structure": {
+                "layout": layout["layout"],
+                "manifests": layout["manifests"],
+                "apply_hint": layout["apply_hint"],
+                // Everything written before this summary, i.e. manifests and reports
+                "uncompressed_bytes": self.bytes_written()
             },
-            "namespace_details": namespace_details
+            "skipped_resources": skipped_resources,
+            "notes": summary.notes,
+            "warnings": summary.warnings
         });
+        if !redactions.paths.is_empty() {
+            summary["redactions"] = serde_json::json!(redactions.paths);
+        }
+        if let Some(secret_keys) = &redactions.secret_keys {
+            summary["secret_data_keys"] = serde_json::json!({
+                "kept": secret_keys.kept,
+                "redacted": secret_keys.redacted
+            });
+        }
 
         let filename = format!("{}/collection-summary.yaml", output_dir);
         info!("Creating enhanced collection summary: {}", filename);
 
         let summary_content =
             serde_yaml::to_string(&summary).context("Failed to serialize summary to YAML")?;
-        fs::write(&filename, summary_content).context("Failed to write YAML summary file")?;
+        self.write_file(&filename, summary_content)
+            .context("Failed to write YAML summary file")?;
+
+        Ok(())
+    }
+
+    /// Create a Markdown report of the collection for pasting into support tickets
+    pub fn create_markdown_report(&self, output_dir: &str, summary: &ClusterSummary) -> Result<()> {
+        let totals = &summary.totals;
+        let mut report = String::new();
+
+        report.push_str("# Ketchup Cluster Report\n\n");
+        if summary.interrupted {
+            report.push_str(
+                "> **Partial collection:** interrupted before all namespaces were saved.\n\n",
+            );
+        }
+        if let Some(crd_schemas) = summary.crd_schemas {
+            report.push_str(&format!(
+                "> **CRD schemas only:** {} CustomResourceDefinitions, no namespaced resources or custom resource instances.\n\n",
+                crd_schemas
+            ));
+        }
+        report.push_str(&format!(
+            "- **Collected at:** {}\n",
+            self.timestamp.to_rfc3339()
+        ));
+        report.push_str(&format!(
+            "- **Kubernetes version:** {}\n",
+            summary.kubernetes_version
+        ));
+        report.push_str(&format!("- **Cluster ID:** {}\n", summary.cluster_id));
+        let cni = summary
+            .cni
+            .as_ref()
+            .map(|cni| format!("{} {}", cni.name, cni.version))
+            .unwrap_or_else(|| "not detected".to_string());
+        report.push_str(&format!("- **CNI:** {}\n", cni));
+        if let Some(ingress) = &summary.ingress {
+            let controllers: Vec<String> = ingress
+                .traefik
+                .iter()
+                .chain(&ingress.other_controllers)
+                .map(|controller| format!("{} {}", controller.name, controller.version))
+                .collect();
+            report.push_str(&format!(
+                "- **Ingress:** {} ({})\n",
+                ingress.state,
+                if controllers.is_empty() {
+                    "no controller found".to_string()
+                } else {
+                    controllers.join(", ")
+                }
+            ));
+        }
+        if let Some(gpu) = &summary.gpu {
+            let source = gpu
+                .operator
+                .as_ref()
+                .or(gpu.device_plugin.as_ref())
+                .map(|workload| format!("{} {}", workload.name, workload.version))
+                .unwrap_or_else(|| "no operator or device plugin found".to_string());
+            report.push_str(&format!(
+                "- **GPU:** {} GPUs on {} nodes ({})\n",
+                gpu.total_gpus, gpu.gpu_nodes, source
+            ));
+        }
+        if let Some(usage) = &summary.resource_usage {
+            report.push_str(&format!(
+                "- **Resource usage:** {:.2} CPU cores, {:.1} GiB memory across {} nodes\n",
+                usage.cpu_cores,
+                usage.memory_bytes / (1024.0 * 1024.0 * 1024.0),
+                usage.nodes
+            ));
+        }
+        report.push_str(&format!(
+            "- **Tool:** ketchup {}\n\n",
+            env!("CARGO_PKG_VERSION")
+        ));
+
+        report.push_str("## Overview\n\n");
+        report.push_str("| Resource | Count |\n");
+        report.push_str("|----------|------:|\n");
+        report.push_str(&format!("| Nodes | {} |\n", summary.nodes.len()));
+        report.push_str(&format!("| Namespaces | {} |\n", summary.namespaces.len()));
+        for (_, label, count) in totals.by_type() {
+            report.push_str(&format!("| {} | {} |\n", label, count));
+        }
+        report.push_str(&format!("| **Total** | **{}** |\n\n", totals.total()));
+
+        report.push_str("## Namespaces\n\n");
+        let mut header = String::from("| Namespace |");
+        let mut separator = String::from("|-----------|");
+        for (_, label, _) in totals.by_type() {
+            header.push_str(&format!(" {} |", label));
+            separator.push_str(&format!("{}:|", "-".repeat(label.len() + 1)));
+        }
+        report.push_str(&format!("{} Total |\n", header));
+        report.push_str(&format!("{}------:|\n", separator));
+        for stats in &summary.namespaces {
+            let mut row = format!("| {} |", stats.namespace);
+            for (_, _, count) in stats.counts.by_type() {
+                row.push_str(&format!(" {} |", count));
+            }
+            report.push_str(&format!("{} {} |\n", row, stats.counts.total()));
+        }
+
+        if !summary.nodes.is_empty() {
+            report.push_str("\n## Nodes\n\n");
+            report.push_str("| Node | Roles | Architecture | OS Image | Kernel | Container Runtime | Kubelet |\n");
+            report.push_str("|------|-------|--------------|----------|--------|-------------------|---------|\n");
+            for node in &summary.nodes {
+                report.push_str(&format!(
+                    "| {} | {} | {} | {} | {} | {} | {} |\n",
+                    node.name,
+                    node.roles.join(", "),
+                    node.architecture,
+                    node.os_image,
+                    node.kernel_version,
+                    node.container_runtime_version,
+                    node.kubelet_version
+                ));
+            }
+        }
+
+        if !summary.skipped.is_empty() {
+            report.push_str("\n## Skipped Resources\n\n");
+            for skipped in &summary.skipped {
+                report.push_str(&format!(
+                    "- {} {}: {}\n",
+                    skipped.count, skipped.kind, skipped.reason
+                ));
+            }
+        }
+
+        if !summary.warnings.is_empty() {
+            report.push_str("\n## Warnings\n\n");
+            for warning in &summary.warnings {
+                report.push_str(&format!("- {}\n", warning));
+            }
+        }
+
+        if !summary.notes.is_empty() {
+            report.push_str("\n## Notes\n\n");
+            for note in &summary.notes {
+                report.push_str(&format!("- {}\n", note));
+            }
+        }
+
+        let filename = format!("{}/cluster-report.md", output_dir);
+        info!("Creating Markdown cluster report: {}", filename);
+        self.write_file(&filename, report)
+            .context("Failed to write Markdown report file")?;
+
+        Ok(())
+    }
+
+    /// Create fleet summary aggregating per-cluster results in the base output directory
+    pub fn create_fleet_summary(&self, results: &[FleetClusterResult]) -> Result<()> {
+        let mut clusters = Vec::new();
+        let mut fleet_totals = ResourceCounts::default();
+        let mut succeeded = 0;
+
+        for result in results {
+            match (&result.summary, &result.error) {
+                (Some(summary), _) => {
+                    succeeded += 1;
+                    fleet_totals.add(&summary.totals);
+                    let mut cluster = serde_json::Map::new();
+                    cluster.insert("name".to_string(), result.name.clone().into());
+                    cluster.insert("context".to_string(), serde_json::json!(result.context));
+                    cluster.insert("status".to_string(), "succeeded".into());
+                    cluster.insert(
+                        "output_dir".to_string(),
+                        serde_json::json!(result.output_dir),
+                    );
+                    cluster.insert(
+                        "duration_seconds".to_string(),
+                        result.duration.as_secs_f64().into(),
+                    );
+                    cluster.insert(
+                        "kubernetes_version".to_string(),
+                        summary.kubernetes_version.clone().into(),
+                    );
+                    cluster.insert(
+                        "total_namespaces".to_string(),
+                        summary.namespaces.len().into(),
+                    );
+                    for (resource_type, _, count) in summary.totals.by_type() {
+                        cluster.insert(format!("total_{}", resource_type), count.into());
+                    }
+                    cluster.insert("total_resources".to_string(), summary.totals.total().into());
+                    cluster.insert("components".to_string(), cluster_components(summary));
+                    clusters.push(Value::Object(cluster));
+                }
+                (None, error) => {
+                    clusters.push(serde_json::json!({
+                        "name": result.name,
+                        "context": result.context,
+                        "status": "failed",
+                        "duration_seconds": result.duration.as_secs_f64(),
+                        "error": error
+                    }));
+                }
+            }
+        }
+
+        let mut fleet_summary = serde_json::Map::new();
+        fleet_summary.insert("total_clusters".to_string(), results.len().into());
+        fleet_summary.insert("succeeded".to_string(), succeeded.into());
+        fleet_summary.insert("failed".to_string(), (results.len() - succeeded).into());
+        for (resource_type, _, count) in fleet_totals.by_type() {
+            fleet_summary.insert(format!("total_{}", resource_type), count.into());
+        }
+        fleet_summary.insert("total_resources".to_string(), fleet_totals.total().into());
+
+        let report = serde_json::json!({
+            "collection_info": {
+                "timestamp": self.timestamp.to_rfc3339(),
+                "tool": "ketchup",
+                "version": env!("CARGO_PKG_VERSION")
+            },
+            "fleet_summary": fleet_summary,
+            "clusters": clusters
+        });
+
+        self.create_dir(&self.base_dir)
+            .context("Failed to create output directory")?;
+        let filename = format!("{}/fleet-summary.yaml", self.base_dir);
+        info!("Creating fleet summary: {}", filename);
+
+        let content =
+            serde_yaml::to_string(&report).context("Failed to serialize fleet summary to YAML")?;
+        self.write_file(&filename, content)
+            .context("Failed to write fleet summary file")?;
 
         Ok(())
     }
@@ -362,6 +963,10 @@ impl OutputManager {
         compression: &str,
     ) -> Result<Option<String>> {
         match compression {
+            "compressed" if self.stream_archive => {
+                let archive_path = self.finish_stream_archive(output_dir)?;
+                Ok(Some(archive_path))
+            }
             "compressed" => {
                 let archive_path = self.create_archive(output_dir)?;
                 Ok(Some(archive_path))
@@ -384,6 +989,45 @@ impl OutputManager {
         }
     }
 
+    /// Log the uncompressed output size and, for an archive, its size and compression ratio.
+    /// The summary inside the archive cannot know the archive's size, so an archive also gets a
+    /// {archive}.summary.yaml sidecar with the final output_structure sizes
+    pub fn report_output_size(&self, archive_path: Option<&str>) -> Result<()> {
+        let uncompressed = self.bytes_written();
+        match archive_path {
+            Some(archive_path) => {
+                let compressed = fs::metadata(archive_path)
+                    .with_context(|| format!("Failed to read size of {}", archive_path))?
+                    .len();
+                let ratio = uncompressed as f64 / compressed.max(1) as f64;
+                info!(
+                    "Output size: {} uncompressed, {} archive ({:.1}x compression)",
+                    format_bytes(uncompressed),
+                    format_bytes(compressed),
+                    ratio
+                );
+
+                let sidecar = serde_json::json!({
+                    "archive": Path::new(archive_path)
+                        .file_name()
+                        .map(|name| name.to_string_lossy()),
+                    "output_structure": {
+                        "uncompressed_bytes": uncompressed,
+                        "compressed_bytes": compressed,
+                        "compression_ratio": (ratio * 100.0).round() / 100.0
+                    }
+                });
+                let filename = format!("{}.summary.yaml", archive_path);
+                let content = serde_yaml::to_string(&sidecar)
+                    .context("Failed to serialize archive summary to YAML")?;
+                self.write_file(&filename, content)
+                    .with_context(|| format!("Failed to write {}", filename))?;
+            }
+            None => info!("Output size: {} uncompressed", format_bytes(uncompressed)),
+        }
+        Ok(())
+    }
+
     /// Create compressed archive of the output directory
     pub fn create_archive(&self, output_dir: &str) -> Result<String> {
         let archive_name = format!("{}.tar.gz", output_dir);
@@ -394,11 +1038,445 @@ impl OutputManager {
         let enc = flate2::write::GzEncoder::new(tar_gz, flate2::Compression::default());
         let mut tar = tar::Builder::new(enc);
 
-        tar.append_dir_all(".", output_dir)
+        tar.append_dir_all(self.archive_root(output_dir), output_dir)
             .context("Failed to add directory to archive")?;
         tar.finish().context("Failed to finalize archive")?;
+        apply_mode(Path::new(&archive_name), self.file_mode)?;
+        info!("Archive created successfully: {}", archive_name);
+
+        Ok(archive_name)
+    }
+
+    /// Finish the archive the output was streamed into and move it to its final name
+    fn finish_stream_archive(&self, output_dir: &str) -> Result<String> {
+        let archive_name = format!("{}.tar.gz", output_dir);
+        let partial_name = format!("{}{}", archive_name, PARTIAL_ARCHIVE_SUFFIX);
+        let archive = self
+            .archive
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+            .context("Streamed archive was not started")?;
+        archive
+            .into_inner()
+            .context("Failed to finalize archive")?
+            .finish()
+            .context("Failed to finalize archive")?
+            .sync_all()
+            .context("Failed to finalize archive")?;
+        fs::rename(&partial_name, &archive_name)
+            .with_context(|| format!("Failed to rename {} to {}", partial_name, archive_name))?;
+        apply_mode(Path::new(&archive_name), self.file_mode)?;
         info!("Archive created successfully: {}", archive_name);
 
         Ok(archive_name)
     }
+
+    /// Path of a file inside the output directory, relative to it, when streaming the archive
+    fn streamed_path<'a>(&self, path: &'a str) -> Option<&'a str> {
+        if !self.stream_archive {
+            return None;
+        }
+        path.strip_prefix(self.output_dir_path().as_str())
+            .and_then(|relative| relative.strip_prefix('/'))
+    }
+
+    /// Comment banner for saved YAML manifests, empty unless --file-header is set. YAML comments
+    /// are ignored by kubectl apply, and JSON has no comment syntax so it never gets one
+    fn yaml_header(&self) -> String {
+        match &self.file_header {
+            Some(source) => format!(
+                "# Collected by ketchup {} from {}\n# Collected at {}\n",
+                env!("CARGO_PKG_VERSION"),
+                source,
+                self.timestamp.to_rfc3339()
+            ),
+            None => String::new(),
+        }
+    }
+
+    /// Delete the output directory after checking that its archive can be read back in full
+    pub fn remove_archived_output(&self, output_dir: &str, archive_path: &str) -> Result<()> {
+        let archive = fs::File::open(archive_path)
+            .with_context(|| format!("Failed to open archive {}", archive_path))?;
+        let unreadable = || {
+            format!(
+                "Archive {} is unreadable, keeping {}",
+                archive_path, output_dir
+            )
+        };
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+        let mut entries = 0;
+        for entry in archive.entries().with_context(unreadable)? {
+            entry.with_context(unreadable)?;
+            entries += 1;
+        }
+        if entries == 0 {
+            anyhow::bail!("Archive {} is empty, keeping {}", archive_path, output_dir);
+        }
+
+        fs::remove_dir_all(output_dir)
+            .with_context(|| format!("Failed to remove output directory {}", output_dir))?;
+        info!(
+            "Removed {} after verifying {} entries in {}",
+            output_dir, entries, archive_path
+        );
+        Ok(())
+    }
+
+    /// Write a file and apply the configured file mode
+    fn write_file(&self, path: &str, contents: impl AsRef<[u8]>) -> Result<()> {
+        let contents = contents.as_ref();
+        self.bytes_written
+            .fetch_add(contents.len() as u64, Ordering::Relaxed);
+
+        if let Some(relative) = self.streamed_path(path) {
+            let entry = Path::new(&self.archive_root(&self.output_dir_path())).join(relative);
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(self.file_mode.unwrap_or(0o644));
+            header.set_mtime(self.timestamp.timestamp().max(0) as u64);
+            let mut archive = self.archive.lock().unwrap_or_else(|e| e.into_inner());
+            return archive
+                .as_mut()
+                .context("Streamed archive is not open")?
+                .append_data(&mut header, &entry, contents)
+                .with_context(|| format!("Failed to add {} to archive", entry.display()));
+        }
+
+        fs::write(path, contents)?;
+        apply_mode(Path::new(path), self.file_mode)
+    }
+
+    /// Create a directory (and missing parents), applying the configured directory mode
+    /// to the directories created here but not to pre-existing ones like the base dir
+    fn create_dir(&self, path: &str) -> Result<()> {
+        // Streamed files carry their directories in their archive paths
+        if self.stream_archive && path.starts_with(self.output_dir_path().as_str()) {
+            return Ok(());
+        }
+        let missing: Vec<PathBuf> = Path::new(path)
+            .ancestors()
+            .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+            .map(Path::to_path_buf)
+            .collect();
+        fs::create_dir_all(path)?;
+        for dir in missing.iter().rev() {
+            apply_mode(dir, self.dir_mode)?;
+        }
+        Ok(())
+    }
+}
+
+/// Components detected in a cluster, as reported in suse-edge-analysis.yaml, for comparing the
+/// clusters of a fleet
+fn cluster_components(summary: &ClusterSummary) -> Value {
+    let ingress_controllers: Vec<String> = summary
+        .ingress
+        .iter()
+        .flat_map(|ingress| ingress.traefik.iter().chain(&ingress.other_controllers))
+        .map(|controller| format!("{} {}", controller.name, controller.version))
+        .collect();
+    serde_json::json!({
+        "cni": summary
+            .cni
+            .as_ref()
+            .map(|cni| format!("{} {}", cni.name, cni.version)),
+        "ingress_controllers": ingress_controllers,
+        "gpus": summary.gpu.as_ref().map_or(0, |gpu| gpu.total_gpus),
+        "rancher_role": summary.rancher_role,
+        "scheduled_backups": summary.scheduled_backups
+    })
+}
+
+/// Which of json, yaml and ndjson files an output format writes
+fn output_formats(format: &str) -> Result<(bool, bool, bool)> {
+    match format {
+        "json" => Ok((true, false, false)),
+        "yaml" => Ok((false, true, false)),
+        "both" => Ok((true, true, false)),
+        "all" => Ok((true, true, true)),
+        _ => Err(anyhow::anyhow!("Invalid format: {}", format)),
+    }
+}
+
+/// Human-readable byte count in binary units
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Set permission bits on a path; a no-op without a mode or on non-Unix platforms
+fn apply_mode(path: &Path, mode: Option<u32>) -> Result<()> {
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+            .with_context(|| format!("Failed to set permissions on {}", path.display()))?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use base64::prelude::BASE64_STANDARD;
+    use flate2::read::GzDecoder;
+
+    /// Fresh scratch directory under the system temp dir, unique per test and process
+    fn scratch_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ketchup-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Paths of every entry in a .tar.gz archive
+    fn archive_entries(archive_path: &str) -> Vec<PathBuf> {
+        let file = fs::File::open(archive_path).unwrap();
+        let mut archive = tar::Archive::new(GzDecoder::new(file));
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn archive_entries_are_prefixed_with_root_dir() {
+        let base = scratch_dir("archive-root");
+        let output_dir = base.join("ketchup-2026-10-15-12-00-00");
+        fs::create_dir_all(output_dir.join("default")).unwrap();
+        fs::write(
+            output_dir.join("collection-summary.yaml"),
+            "namespaces: 1\n",
+        )
+        .unwrap();
+        fs::write(
+            output_dir.join("default/configmap-app.yaml"),
+            "kind: ConfigMap\n",
+        )
+        .unwrap();
+        let output_dir = output_dir.to_str().unwrap();
+
+        for (tar_root_dir, expected_root) in [
+            (None, "ketchup-2026-10-15-12-00-00"),
+            (Some("support-bundle".to_string()), "support-bundle"),
+        ] {
+            let manager = OutputManager::new_output_manager(base.to_string_lossy().into_owned())
+                .with_tar_root_dir(tar_root_dir);
+            let archive_path = manager.create_archive(output_dir).unwrap();
+
+            let entries = archive_entries(&archive_path);
+            assert!(entries.len() >= 3, "unexpected entries: {:?}", entries);
+            for entry in &entries {
+                assert!(
+                    entry.starts_with(expected_root),
+                    "{} is not under {}",
+                    entry.display(),
+                    expected_root
+                );
+            }
+            assert!(entries.contains(&Path::new(expected_root).join("default/configmap-app.yaml")));
+        }
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn configmap_binary_data_survives_yaml_round_trip() {
+        let base = scratch_dir("binary-data");
+        let dir = base.to_str().unwrap();
+        // Payloads whose base64 is empty, reads as a YAML number, or is full of + and /
+        let binary_data: serde_json::Map<String, Value> = [
+            ("empty.bin", &[][..]),
+            ("digits.bin", &[0xd7, 0x6d, 0xf8][..]),
+            ("null-bytes.bin", &[0x00, 0x00, 0x00, 0xff, 0xfe, 0xfd][..]),
+            ("plus-slash.bin", &[0xfb, 0xff, 0xbf, 0xfb, 0xef, 0xfe][..]),
+            ("padded.bin", &[0x9e, 0xe9, 0x65, 0x00][..]),
+        ]
+        .into_iter()
+        .map(|(key, bytes)| {
+            let encoded = BASE64_STANDARD.encode(bytes);
+            (key.to_string(), Value::String(encoded))
+        })
+        .collect();
+        let configmap = serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": {"name": "firmware", "namespace": "default"},
+            "binaryData": binary_data,
+        });
+
+        let manager = OutputManager::new_output_manager(dir.to_string());
+        manager
+            .write_resource(dir, "configmap-firmware", &configmap, "yaml")
+            .unwrap();
+
+        let yaml = fs::read_to_string(base.join("configmap-firmware.yaml")).unwrap();
+        let parsed: Value = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(parsed["binaryData"], configmap["binaryData"]);
+        for (key, encoded) in &binary_data {
+            let original = BASE64_STANDARD.decode(encoded.as_str().unwrap()).unwrap();
+            let round_tripped = BASE64_STANDARD
+                .decode(parsed["binaryData"][key].as_str().unwrap())
+                .unwrap();
+            assert_eq!(round_tripped, original, "{} changed", key);
+        }
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn fleet_summary_lists_components_per_cluster() {
+        let base = scratch_dir("fleet-summary");
+        let mut summary = ClusterSummary::new("v1.30.4+k3s1".to_string(), Vec::new());
+        summary.cni = Some(CniInfo {
+            name: "Cilium".to_string(),
+            category: "Networking".to_string(),
+            version: "v1.15.6".to_string(),
+            daemonset: Some("kube-system/cilium".to_string()),
+            crd: Some("ciliumnetworkpolicies.cilium.io".to_string()),
+        });
+        summary.rancher_role = Some("management".to_string());
+        summary.scheduled_backups = Some(1);
+        let results = [
+            FleetClusterResult {
+                name: "edge-01".to_string(),
+                context: None,
+                output_dir: Some("edge-01".to_string()),
+                summary: Some(summary),
+                error: None,
+                duration: Duration::from_secs(3),
+            },
+            FleetClusterResult {
+                name: "edge-02".to_string(),
+                context: None,
+                output_dir: None,
+                summary: None,
+                error: Some("connection refused".to_string()),
+                duration: Duration::from_secs(1),
+            },
+        ];
+
+        let manager = OutputManager::new_output_manager(base.to_string_lossy().into_owned());
+        manager.create_fleet_summary(&results).unwrap();
+
+        let report: Value =
+            serde_yaml::from_str(&fs::read_to_string(base.join("fleet-summary.yaml")).unwrap())
+                .unwrap();
+        let components = &report["clusters"][0]["components"];
+        assert_eq!(components["cni"], "Cilium v1.15.6");
+        assert_eq!(components["ingress_controllers"], serde_json::json!([]));
+        assert_eq!(components["gpus"], 0);
+        assert_eq!(components["rancher_role"], "management");
+        assert_eq!(components["scheduled_backups"], 1);
+        assert!(report["clusters"][1].get("components").is_none());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn flattened_cluster_scoped_resources_use_cluster_prefix() {
+        let base = scratch_dir("flatten-cluster");
+        let dir = base.to_str().unwrap();
+        let crd = serde_json::json!({
+            "apiVersion": "apiextensions.k8s.io/v1",
+            "kind": "CustomResourceDefinition",
+            "metadata": {"name": "helmcharts.helm.cattle.io"},
+        });
+
+        let manager = OutputManager::new_output_manager(dir.to_string()).with_flatten(true);
+        manager
+            .save_resources_individually(
+                dir,
+                CLUSTER_SCOPED_DIR,
+                "customresourcedefinitions",
+                &[crd],
+                "yaml",
+            )
+            .unwrap();
+
+        assert!(
+            base.join(
+                "manifests/cluster__CustomResourceDefinition__helmcharts.helm.cattle.io.yaml"
+            )
+            .exists()
+        );
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn streamed_archive_is_partial_until_finished() {
+        let base = scratch_dir("stream-archive");
+        let manager = OutputManager::new_output_manager(base.to_string_lossy().into_owned())
+            .with_stream_archive(true);
+        let output_dir = manager.create_output_directory().unwrap();
+        let archive_name = format!("{}.tar.gz", output_dir);
+        let partial_name = format!("{}{}", archive_name, PARTIAL_ARCHIVE_SUFFIX);
+
+        manager
+            .write_yaml_report(&output_dir, "report.yaml", &serde_json::json!({"ok": true}))
+            .unwrap();
+        assert!(Path::new(&partial_name).exists());
+        assert!(!Path::new(&archive_name).exists());
+
+        let archive_path = manager
+            .handle_compression(&output_dir, "compressed")
+            .unwrap();
+        assert_eq!(archive_path.as_deref(), Some(archive_name.as_str()));
+        assert!(!Path::new(&partial_name).exists());
+        let root = manager.archive_root(&output_dir);
+        assert!(archive_entries(&archive_name).contains(&Path::new(&root).join("report.yaml")));
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn archive_sizes_are_written_next_to_the_archive() {
+        let base = scratch_dir("archive-sizes");
+        let output_dir = base.join("ketchup-2026-10-15-12-00-00");
+        fs::create_dir_all(&output_dir).unwrap();
+        let output_dir = output_dir.to_str().unwrap();
+
+        let manager = OutputManager::new_output_manager(base.to_string_lossy().into_owned());
+        manager
+            .write_yaml_report(
+                output_dir,
+                "report.yaml",
+                &serde_json::json!({"a": "b".repeat(4096)}),
+            )
+            .unwrap();
+        let archive_path = manager.create_archive(output_dir).unwrap();
+        manager.report_output_size(Some(&archive_path)).unwrap();
+
+        let sidecar: Value = serde_yaml::from_str(
+            &fs::read_to_string(format!("{}.summary.yaml", archive_path)).unwrap(),
+        )
+        .unwrap();
+        let sizes = &sidecar["output_structure"];
+        assert_eq!(sidecar["archive"], "ketchup-2026-10-15-12-00-00.tar.gz");
+        assert!(sizes["uncompressed_bytes"].as_u64().unwrap() > 4096);
+        assert_eq!(
+            sizes["compressed_bytes"].as_u64(),
+            Some(fs::metadata(&archive_path).unwrap().len())
+        );
+        assert!(sizes["compression_ratio"].as_f64().unwrap() > 1.0);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
 }
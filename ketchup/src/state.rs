@@ -0,0 +1,54 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+/// State file kept in the output directory while resources are being saved
+pub const STATE_FILE: &str = ".ketchup-state.json";
+
+/// Which (namespace, resource type) pairs have been saved, so --resume can skip them
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CollectionState {
+    /// Saved resource count per namespace and resource type
+    completed: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+impl CollectionState {
+    /// Load the state of an earlier run, or start empty if it left no state file
+    pub fn load(output_dir: &str) -> Result<Self> {
+        let path = Path::new(output_dir).join(STATE_FILE);
+        if !path.exists() {
+            info!("No {} in {}, nothing to resume", STATE_FILE, output_dir);
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Number of resources saved for a pair, if it was completed
+    pub fn completed_count(&self, namespace: &str, resource_type: &str) -> Option<usize> {
+        self.completed
+            .get(namespace)
+            .and_then(|types| types.get(resource_type))
+            .copied()
+    }
+
+    /// Whether every given resource type has been saved for a namespace
+    pub fn is_namespace_complete(&self, namespace: &str, resource_types: &[&str]) -> bool {
+        resource_types
+            .iter()
+            .all(|resource_type| self.completed_count(namespace, resource_type).is_some())
+    }
+
+    pub fn record(&mut self, namespace: &str, resource_type: &str, count: usize) {
+        self.completed
+            .entry(namespace.to_string())
+            .or_default()
+            .insert(resource_type.to_string(), count);
+    }
+}
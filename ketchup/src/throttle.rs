@@ -0,0 +1,43 @@
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::debug;
+
+/// Token bucket shared by all collectors to cap API requests per second. Allows a burst
+/// of one second's worth of requests, then spaces them evenly.
+pub struct RateLimiter {
+    interval: Duration,
+    burst_window: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(qps: u32) -> Self {
+        let qps = qps.max(1);
+        let interval = Duration::from_secs(1) / qps;
+        Self {
+            interval,
+            burst_window: interval * (qps - 1),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until another request may be sent
+    pub async fn acquire(&self, request: &str) {
+        let delay = {
+            let mut next_slot = self.next_slot.lock().await;
+            let now = Instant::now();
+            let slot = (*next_slot).max(now);
+            *next_slot = slot + self.interval;
+            (slot - now).saturating_sub(self.burst_window)
+        };
+
+        if !delay.is_zero() {
+            debug!(
+                "Throttling {} for {:?} to stay under the QPS limit",
+                request, delay
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}